@@ -1,42 +1,207 @@
 use std::{
-    io::{Seek, SeekFrom, BufWriter},
-    fs::File,
+    io::{Seek, SeekFrom, BufWriter, Write},
+    fs,
+    path::{Path, PathBuf},
+    collections::{BTreeSet, HashMap},
 };
 
 use crate::{
     threads::ThreadPool,
     progress::Progress,
-    config::{Config, Align, Method},
+    config::{Config, Align, Method, Filter},
     buffered_io::{
         BufferedRead,
-        new_input_file, new_output_file,
+        new_input_file, new_input_archive, new_output_archive,
     },
     error::ArchiveError,
     block::Block,
     archiveinfo::ArchiveInfo,
-    filedata::Type,
+    filedata::{FileData, Type, Entry},
+    crc32::{Crc32, Crc32Stream},
+    sign::{self, Signer, TRAILER_LEN},
+    volume::{self, Volumes},
+    crypto::Keyring,
+    recovery,
 };
 
 
-/// An existing archive and associated information.
-struct Archive {
-    file:  BufWriter<File>,
-    info:  ArchiveInfo,
+/// An archive being written, either an archive file or stdout, and 
+/// associated information.
+struct Archive<W: Write> {
+    file:    BufWriter<W>,
+    info:    ArchiveInfo,
+    pos:     u64,            // Current position in archive
+    signer:  Option<Signer>, // Hashes written blocks, if signing
 }
-impl Archive {
-    fn new(cfg: &Config) -> Result<Archive, ArchiveError> {
+impl Archive<Volumes> {
+    /// Open a new or existing archive file or volume set, positioned at 
+    /// the end of existing data.
+    fn open(cfg: &Config) -> Result<Archive<Volumes>, ArchiveError> {
         let info = ArchiveInfo::new(&cfg.arch)?;
-        let mut file = new_output_file(&cfg.arch, cfg.clobber)?;
+        let mut signer = cfg.sign_key.as_ref().map(Signer::new);
+
+        // Existing blocks are covered by the new signature
+        if let Some(signer) = &mut signer {
+            if info.end_of_data() > 0 {
+                signer.update_from(&mut new_input_archive(&cfg.arch.path)?, info.end_of_data())?;
+            }
+        }
+        let mut file = new_output_archive(&cfg.arch, cfg.volume_size, cfg.clobber)?;
         file.seek(SeekFrom::Start(info.end_of_data()))?;
+        Ok(Archive::new(info, file, signer))
+    }
+
+    /// Truncate any data left over from the previous end of the archive,
+    /// then add a recovery record with 'recovery' percent redundancy.
+    fn close(mut file: Volumes, recovery: Option<u8>) -> Result<(), ArchiveError> {
+        let len = file.stream_position()?;
+        file.set_len(len)?;
+        if let Some(pct) = recovery {
+            recovery::write_record(&mut file, pct)?;
+        }
+        Ok(())
+    }
+}
+impl<W: Write> Archive<W> {
+    fn new(info: ArchiveInfo, file: BufWriter<W>, signer: Option<Signer>) -> Archive<W> {
+        Archive {
+            pos: info.end_of_data(),
+            info, file, signer,
+        }
+    }
 
-        Ok(
-            Archive {
-                info, file
+    /// Write a block and record its position.
+    fn write_block(&mut self, blk: Block) {
+        let pos = self.pos;
+        match &mut self.signer {
+            Some(signer) => {
+                let mut buf = BufWriter::new(Vec::new());
+                blk.write_to(&mut buf);
+                let buf = buf.into_inner().unwrap();
+                signer.update(&buf);
+                self.file.write_all(&buf).unwrap();
             }
-        )
+            None => blk.write_to(&mut self.file),
+        }
+        self.pos += blk.size();
+
+        if blk.is_empty() {
+            self.info.set_end_of_data(pos);
+        }
+        else {
+            self.info.push(pos, blk);
+        }
+    }
+
+    /// Write signature and index if requested and return the underlying 
+    /// writer. Archives written to stdout are always signed inline.
+    fn finish(mut self, cfg: &Config) -> Result<W, ArchiveError> {
+        if let Some(signer) = self.signer.take() {
+            let trailer = signer.finish();
+            if cfg.detached && !cfg.arch.is_stdio() {
+                trailer.write_detached(&sign::detached_path(&cfg.arch.path))?;
+            }
+            else {
+                trailer.write_to(&mut self.file);
+                self.pos += TRAILER_LEN;
+            }
+        }
+        if cfg.index {
+            self.info.write_index(&mut self.file, self.pos);
+        }
+        self.file.into_inner().map_err(|err| err.into_error().into())
     }
 }
 
+/// Return the filter to apply to a block, either the one specified by
+/// the user or the one covering the most bytes in the block, where each 
+/// file's filter comes from its extension rule or its type.
+fn select_filter(blk: &Block, filter: Option<Filter>, rules: &[(String, Filter)], words: bool) -> Filter {
+    if let Some(filter) = filter {
+        return filter;
+    }
+    if blk.method == Method::Store {
+        return Filter::None;
+    }
+
+    let mut totals: Vec<(Filter, u64)> = Vec::new();
+
+    for file in blk.files.iter() {
+        let ext = file.path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+        let filter = match rules.iter().find(|(rule, _)| rule.eq_ignore_ascii_case(ext)) {
+            Some((_, filter)) => *filter,
+            None => {
+                match file.kind {
+                    Type::Executable => Filter::X86,
+                    Type::Text if words => Filter::Words,
+                    _ => Filter::None,
+                }
+            }
+        };
+        let len = file.seg_end - file.seg_beg;
+
+        match totals.iter_mut().find(|(f, _)| *f == filter) {
+            Some((_, total)) => *total += len,
+            None => totals.push((filter, len)),
+        }
+    }
+    totals.into_iter()
+        .max_by_key(|(_, total)| *total)
+        .map(|(filter, _)| filter)
+        .unwrap_or_default()
+}
+
+/// Check if 'path' or any of its parent directories matches 'pattern',
+/// where '*' matches any run of characters and '?' any one character.
+fn matches(pattern: &str, path: &Path) -> bool {
+    let pattern = pattern.chars().collect::<Vec<char>>();
+    path.ancestors().any(|path| {
+        glob(&pattern, &path.to_string_lossy().chars().collect::<Vec<char>>())
+    })
+}
+
+/// Match a whole string against a wildcard pattern, backtracking to the
+/// last '*' on a mismatch.
+fn glob(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut star = None; // Pattern position after last '*' and text position it's matched to
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        }
+        else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p + 1, t));
+            p += 1;
+        }
+        else if let Some((star_p, star_t)) = star {
+            star = Some((star_p, star_t + 1));
+            p = star_p;
+            t = star_t + 1;
+        }
+        else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Remove the data of files not kept from a decompressed block, moving
+/// the remaining segments together.
+fn strip_block(blk: &mut Block, keep: impl Fn(&FileData) -> bool) {
+    let mut data = Vec::with_capacity(blk.data.len());
+    blk.files.retain(|file| keep(file));
+
+    for file in blk.files.iter_mut() {
+        let beg = file.blk_pos as usize;
+        let end = beg + (file.seg_end - file.seg_beg) as usize;
+        file.blk_pos = data.len() as u64;
+        data.extend_from_slice(&blk.data[beg..end]);
+    }
+    blk.data = data;
+}
+
 /// An archive consists of blocks, with each block containing a
 /// header followed by compressed data. Blocks can either be fixed size,
 /// or truncated to align with the end of the current file. The end of an
@@ -56,70 +221,106 @@ impl Archiver {
     /// and append_files() since creating a new archive can be thought of
     /// as appending files to an existing empty archive.
     fn archive(&mut self) -> Result<(), ArchiveError> {
-        let mut archive = Archive::new(&self.cfg)?;
+        let recovery = self.recovery()?;
+        let archive = Archive::open(&self.cfg)?;
+        Archive::close(self.write_archive(archive)?, recovery)
+    }
+
+    /// Redundancy of the recovery record to write, either the one given
+    /// or the existing archive's, which is rewritten after appending.
+    fn recovery(&self) -> Result<Option<u8>, ArchiveError> {
+        match self.cfg.recovery {
+            Some(pct) => Ok(Some(pct)),
+            None => recovery::redundancy(&self.cfg.arch),
+        }
+    }
+
+    /// Read inputs into blocks, compress them and write them to archive, 
+    /// returning the underlying writer.
+    fn write_archive<W: Write>(&mut self, mut archive: Archive<W>) -> Result<W, ArchiveError> {
         let mut tp = ThreadPool::new(archive.info.next_id(), &self.cfg);
         let mut blk = Block::new(&self.cfg);
     
         // Read files into blocks and compress
         for file in self.cfg.inputs.iter_mut() {
+            // Links and directories have no data
+            if file.entry != Entry::File {
+                blk.files.push(file.clone());
+                continue;
+            }
+
             // If remaining files are already compressed, compress
             // current block and switch method to store.
             if file.kind == Type::Compressed {
-                if !blk.data.is_empty() {
-                    tp.compress_block(blk.clone());
+                if !blk.is_empty() {
+                    blk.filter = select_filter(&blk, self.cfg.filter, &self.cfg.filter_ext, self.cfg.words);
+                    tp.compress_block(blk.clone())?;
+                    blk.next();
                 }
-                blk.next();
                 blk.method = Method::Store;
             }
 
             let mut file_in = new_input_file(&file.path)?;
+            let mut crc = Crc32Stream::default();
             file.blk_pos = blk.data.len() as u64;
 
             for _ in 0..file.len {
-                blk.data.push(file_in.read_byte());
+                let byte = file_in.read_byte();
+                crc.update(&[byte]);
+                blk.data.push(byte);
                 if blk.data.len() >= self.cfg.blk_sz {
                     let pos = file_in.stream_position()?;
                     file.seg_end = pos;
+                    if pos == file.len {
+                        file.chksum = crc.finish();
+                    }
                     blk.files.push(file.clone());
-                    tp.compress_block(blk.clone());
+                    blk.filter = select_filter(&blk, self.cfg.filter, &self.cfg.filter_ext, self.cfg.words);
+                    tp.compress_block(blk.clone())?;
                     blk.next();
                     file.blk_pos = 0;
                     file.seg_beg = pos;
                 }
             }
             file.seg_end = file_in.stream_position()?;
+            file.chksum = crc.finish();
 
             // Truncate block to align with end of file
             if self.cfg.align == Align::File && !blk.data.is_empty() {
                 blk.files.push(file.clone());
-                tp.compress_block(blk.clone());
+                blk.filter = select_filter(&blk, self.cfg.filter, &self.cfg.filter_ext, self.cfg.words);
+                tp.compress_block(blk.clone())?;
                 blk.next();
                 file.seg_beg = file_in.stream_position()?;
             }
-            if !blk.files.contains(file) {
+            // Skip empty segment left when the file ended a block
+            let remaining = file.seg_beg < file.seg_end || file.len == 0;
+            if remaining && !blk.files.contains(file) {
                 blk.files.push(file.clone());
             }
         }
 
         // Compress final block
-        if !blk.data.is_empty() {
-            tp.compress_block(blk.clone());
+        if !blk.is_empty() {
+            blk.filter = select_filter(&blk, self.cfg.filter, &self.cfg.filter_ext, self.cfg.words);
+            tp.compress_block(blk.clone())?;
             blk.next();
         }
 
         // Empty sentinel block
-        tp.compress_block(blk.clone());
+        tp.compress_block(blk.clone())?;
 
         // Output blocks
         loop {
-            if let Some(blk) = tp.bq.lock().unwrap().try_get_block() {
-                blk.write_to(&mut archive.file);
-                if blk.data.is_empty() { 
+            if let Some(blk) = tp.bq.lock().unwrap().try_get_block()? {
+                let sentinel = blk.is_empty();
+                archive.write_block(blk);
+                if sentinel { 
                     break; 
                 }
             }
         }
-        Ok(())
+        archive.finish(&self.cfg)
     }
 
     /// Parse files into blocks and compress blocks.
@@ -127,13 +328,64 @@ impl Archiver {
         self.archive()
     }
 
+    /// Parse files into blocks and compress blocks, writing a new archive
+    /// to any writer, such as stdout or a Vec, and returning the writer.
+    pub fn create_archive_to<W: Write>(&mut self, writer: W) -> Result<W, ArchiveError> {
+        let signer = self.cfg.sign_key.as_ref().map(Signer::new);
+        let archive = Archive::new(ArchiveInfo::default(), BufWriter::new(writer), signer);
+        self.write_archive(archive)
+    }
+
     /// Add files to existing archive.
     pub fn append_files(&mut self) -> Result<(), ArchiveError> {
         self.archive()
     }
 
+    /// Append only inputs that are new, or changed since their newest 
+    /// entry in the archive, returning the paths appended. Inputs are 
+    /// compared by entry type, length and modification time, and also by
+    /// checksum if requested.
+    pub fn update_files(&mut self) -> Result<Vec<PathBuf>, ArchiveError> {
+        let mut file_in = new_input_archive(&self.cfg.arch.path)?;
+        let mut info = ArchiveInfo::from_reader(&mut file_in)?;
+        info.decrypt_files(&mut file_in, &mut Keyring::new(&self.cfg))?;
+        drop(file_in);
+
+        // Later entries replace earlier ones, and a file's checksum is
+        // stored with its last segment.
+        let mut newest = HashMap::new();
+        for file in info.blocks().flat_map(|(_, hdr)| hdr.files.iter()) {
+            if file.seg_end == file.len {
+                newest.insert(&file.path, file);
+            }
+        }
+
+        let checksum = self.cfg.checksum;
+        self.cfg.inputs.retain(|input| {
+            match newest.get(&input.path) {
+                Some(entry) => {
+                    entry.entry != input.entry 
+                    || entry.len != input.len 
+                    || entry.mtime != input.mtime
+                    || (checksum && input.entry == Entry::File && input.path.crc32() != entry.chksum)
+                }
+                None => true,
+            }
+        });
+        if self.cfg.inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let paths = self.cfg.inputs.iter()
+            .map(|input| input.path.clone())
+            .collect::<Vec<PathBuf>>();
+        self.archive()?;
+        Ok(paths)
+    }
+
     pub fn merge_archives(&mut self) -> Result<(), ArchiveError> {
-        let mut archive = Archive::new(&self.cfg)?;
+        let recovery = self.recovery()?;
+        let mut archive = Archive::open(&self.cfg)?;
         let mut prg = Progress::new(&self.cfg);
 
         let mut blk = Block::default();
@@ -145,20 +397,173 @@ impl Archiver {
         }
 
         for file in self.cfg.inputs.iter() {
-            let mut file_in = new_input_file(&file.path)?;
+            let mut file_in = new_input_archive(&file.path)?;
             loop {
                 blk.read_from(&mut file_in)?;
-                if blk.data.is_empty() {
+                if blk.is_empty() {
                     break;
                 }
                 blk.id = archive.info.next_id();
-                blk.write_to(&mut archive.file);
                 prg.update(&blk);
-                blk.next();
+                archive.write_block(std::mem::take(&mut blk));
             }
         }
         blk.id = archive.info.next_id();
-        blk.write_to(&mut archive.file);
-        Ok(())
+        archive.write_block(blk);
+        Archive::close(archive.finish(&self.cfg)?, recovery)
+    }
+
+    /// Remove files matching any input path or pattern from an existing 
+    /// archive, returning the paths removed. Blocks holding only kept 
+    /// files are copied as is, while blocks holding both kept and removed
+    /// files are decompressed, stripped of removed files and recompressed
+    /// with their original method. The archive is rewritten to a 
+    /// temporary file which then replaces it. A removed file's data is 
+    /// kept for hard links to it that aren't removed, and a detached 
+    /// signature is removed unless the archive is signed detached again.
+    pub fn delete_files(&mut self) -> Result<Vec<PathBuf>, ArchiveError> {
+        let recovery = self.recovery()?;
+        let mut file_in = new_input_archive(&self.cfg.arch.path)?;
+        let mut info = ArchiveInfo::from_reader(&mut file_in)?;
+        info.decrypt_files(&mut file_in, &mut Keyring::new(&self.cfg))?;
+
+        let patterns = self.cfg.inputs.iter()
+            .map(|file| file.path.to_string_lossy().into_owned())
+            .collect::<Vec<String>>();
+        let deleted = |path: &Path| patterns.iter().any(|pattern| matches(pattern, path));
+
+        let paths = info.blocks()
+            .flat_map(|(_, hdr)| hdr.files.iter())
+            .filter(|file| deleted(&file.path))
+            .map(|file| file.path.clone())
+            .collect::<BTreeSet<PathBuf>>();
+        if paths.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // A deleted file with kept hard links to it is renamed to the first
+        // kept link, which is dropped, and other links point to it instead
+        let mut renames: HashMap<PathBuf, PathBuf> = HashMap::new();
+        for file in info.blocks().flat_map(|(_, hdr)| hdr.files.iter()) {
+            if let Entry::Hardlink(target) = &file.entry {
+                if deleted(target) && !deleted(&file.path) && !renames.contains_key(target) {
+                    renames.insert(target.clone(), file.path.clone());
+                }
+            }
+        }
+        let renamed = renames.values().collect::<BTreeSet<&PathBuf>>();
+        let removed = |file: &FileData| {
+            match &file.entry {
+                Entry::Hardlink(_) if renamed.contains(&file.path) => true,
+                Entry::File => deleted(&file.path) && !renames.contains_key(&file.path),
+                _ => deleted(&file.path),
+            }
+        };
+        let relinked = |file: &FileData| {
+            match &file.entry {
+                Entry::File => renames.contains_key(&file.path),
+                Entry::Hardlink(target) => renames.contains_key(target),
+                _ => false,
+            }
+        };
+
+        // Copied blocks bypass the pools, so their progress would be wrong
+        let mut cfg = self.cfg.clone();
+        cfg.quiet = true;
+        let mut dp = ThreadPool::new(0, &cfg);
+        let mut cp = ThreadPool::new(0, &cfg);
+        let mixed = |hdr: &Block| {
+            (hdr.files.iter().any(&removed) || hdr.files.iter().any(&relinked)) && 
+            !hdr.files.iter().all(&removed)
+        };
+
+        // Decompress blocks with both kept and removed files, or renamed files
+        for (id, (pos, hdr)) in info.blocks().filter(|(_, hdr)| mixed(hdr)).enumerate() {
+            // Rewritten blocks must not be written unencrypted
+            if hdr.crypt.is_some() && cfg.password.is_none() && cfg.recipients.is_empty() {
+                return Err(ArchiveError::EncryptionKeyRequired(hdr.id));
+            }
+            let mut blk = Block::default();
+            file_in.seek(SeekFrom::Start(pos))?;
+            blk.read_from(&mut file_in)?;
+            blk.id = id as u32;
+            dp.decompress_block(blk)?;
+        }
+
+        // Copy or recompress remaining blocks in order, renumbering them
+        let mut id = 0;
+        for (pos, hdr) in info.blocks() {
+            if hdr.files.iter().all(&removed) {
+                continue;
+            }
+            if mixed(hdr) {
+                let mut blk = loop {
+                    if let Some(blk) = dp.bq.lock().unwrap().try_get_block()? {
+                        break blk;
+                    }
+                };
+                strip_block(&mut blk, |file| !removed(file));
+                for file in blk.files.iter_mut() {
+                    match &mut file.entry {
+                        Entry::Hardlink(target) => {
+                            if let Some(path) = renames.get(target) {
+                                *target = path.clone();
+                            }
+                        }
+                        _ => {
+                            if let Some(path) = renames.get(&file.path) {
+                                file.path = path.clone();
+                            }
+                        }
+                    }
+                }
+                blk.id = id;
+                cp.compress_block(blk)?;
+            }
+            else {
+                let mut blk = Block::default();
+                file_in.seek(SeekFrom::Start(pos))?;
+                blk.read_from(&mut file_in)?;
+                blk.id = id;
+                cp.copy_block(blk);
+            }
+            id += 1;
+        }
+
+        // Sentinel block
+        let mut blk = Block::default();
+        file_in.seek(SeekFrom::Start(info.end_of_data()))?;
+        blk.read_from(&mut file_in)?;
+        blk.id = id;
+        cp.copy_block(blk);
+
+        let tmp = volume::temp_path(&self.cfg.arch.path);
+        let size = self.cfg.volume_size.or(volume::split_size(&self.cfg.arch.path));
+        let file = new_output_archive(&FileData::new(tmp.clone()), size, true)?;
+        let signer = self.cfg.sign_key.as_ref().map(Signer::new);
+        let mut archive = Archive::new(ArchiveInfo::default(), file, signer);
+
+        loop {
+            if let Some(blk) = cp.bq.lock().unwrap().try_get_block()? {
+                let sentinel = blk.is_empty();
+                archive.write_block(blk);
+                if sentinel {
+                    break;
+                }
+            }
+        }
+        Archive::close(archive.finish(&self.cfg)?, recovery)?;
+        drop(file_in);
+        volume::replace(&tmp, &self.cfg.arch.path)?;
+
+        // A detached signature is only rewritten when signing detached
+        let sig = sign::detached_path(&self.cfg.arch.path);
+        if sig.exists() && !(self.cfg.sign_key.is_some() && self.cfg.detached) {
+            fs::remove_file(&sig)?;
+            if !self.cfg.quiet {
+                println!("Removed signature {}, which no longer matches the archive.", sig.display());
+            }
+        }
+        Ok(paths.into_iter().collect())
     }
 }
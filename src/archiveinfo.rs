@@ -1,39 +1,72 @@
 use std::{
-    io::{Seek, SeekFrom},
+    io::{Read, Seek, SeekFrom, BufReader, BufWriter, Write},
     fmt,
 };
 
 use crate::{
     block::Block,
     filedata::FileData,
-    buffered_io::new_input_file,
+    buffered_io::{BufferedRead, BufferedWrite, new_input_archive},
     error::ArchiveError,
-    constant::Version,
+    crypto::Keyring,
+    recovery,
+    constant::{Version, INDEX_MAGIC, FOOTER_LEN},
 };
 
+// An archive can optionally end with an index, written after the 
+// sentinel block, so the archive can be listed without reading every
+// block header:
+//
+//  End of data (sentinel block position)  u64
+//  Number of blocks                        u32
+//  For each block:
+//      Block position                      u64
+//      Block header
+//  Footer:
+//      Index position                      u64
+//      INDEX_MAGIC                         u32
+
 #[derive(Default, Clone)]
 pub struct ArchiveInfo {
     eod:      u64,
     blks:     Vec<Block>,
+    offsets:  Vec<u64>,
     pub ver:  Version,
     next_id:  u32,
 }
 impl ArchiveInfo {
     pub fn new(arch: &FileData) -> Result<ArchiveInfo, ArchiveError> {
-        let mut info = ArchiveInfo::default();
         if arch.new {
-            return Ok(info);
+            return Ok(ArchiveInfo::default());
+        }
+        ArchiveInfo::from_reader(&mut new_input_archive(&arch.path)?)
+    }
+    /// Read block headers from an archive in any seekable reader.
+    pub fn from_reader<R: Read + Seek>(archive: &mut BufReader<R>) -> Result<ArchiveInfo, ArchiveError> {
+        let mut info = ArchiveInfo::default();
+
+        if !info.read_index(archive)? {
+            return ArchiveInfo::from_headers(archive);
         }
-        let mut archive = new_input_file(&arch.path)?;
+        info.next_id = info.blks.len() as u32;
+        Ok(info)
+    }
+    /// Read block headers by walking every block from the start of the 
+    /// archive, ignoring any index. Unlike the index, which is written 
+    /// after the signature trailer, the headers read this way are signed.
+    pub fn from_headers<R: Read + Seek>(archive: &mut BufReader<R>) -> Result<ArchiveInfo, ArchiveError> {
+        let mut info = ArchiveInfo::default();
         let mut blk = Block::default();
-    
+
+        archive.rewind()?;
         loop {
             info.eod = archive.stream_position()?;
-            blk.read_header_from(&mut archive)?;
-            if blk.sizeo == 0 {
+            blk.read_header_from(archive)?;
+            if blk.sizeo == 0 && blk.files.is_empty() {
                 break;
             }
             info.ver = blk.ver;
+            info.offsets.push(info.eod);
             info.blks.push(blk.clone());
             archive.seek(SeekFrom::Current(blk.sizeo as i64))?;
             blk.next();
@@ -41,9 +74,83 @@ impl ArchiveInfo {
         info.next_id = info.blks.len() as u32;
         Ok(info)
     }
+    /// Read block headers from index, if archive has one.
+    fn read_index<R: Read + Seek>(&mut self, archive: &mut BufReader<R>) -> Result<bool, ArchiveError> {
+        // Index is written before any recovery record
+        let len = match recovery::record_pos(archive)? {
+            Some(pos) => pos,
+            None => archive.seek(SeekFrom::End(0))?,
+        };
+        if len < FOOTER_LEN {
+            archive.rewind()?;
+            return Ok(false);
+        }
+
+        archive.seek(SeekFrom::Start(len - FOOTER_LEN))?;
+        let pos   = archive.read_u64();
+        let magic = archive.read_u32();
+        if magic != INDEX_MAGIC || pos >= len {
+            archive.rewind()?;
+            return Ok(false);
+        }
+
+        archive.seek(SeekFrom::Start(pos))?;
+        self.eod = archive.read_u64();
+        let count = archive.read_u32();
+
+        let mut blk = Block::default();
+        for _ in 0..count {
+            self.offsets.push(archive.read_u64());
+            blk.read_header_from(archive)?;
+            self.ver = blk.ver;
+            self.blks.push(blk.clone());
+            blk.next();
+        }
+        Ok(true)
+    }
+    /// Write index of all blocks and footer, starting at position 'pos'.
+    pub fn write_index<W: Write>(&self, archive: &mut BufWriter<W>, pos: u64) {
+        archive.write_u64(self.eod);
+        archive.write_u32(self.blks.len() as u32);
+        for (offset, blk) in self.offsets.iter().zip(self.blks.iter()) {
+            archive.write_u64(*offset);
+            blk.write_header_to(archive);
+        }
+
+        archive.write_u64(pos);
+        archive.write_u32(INDEX_MAGIC);
+    }
+    /// Read and decrypt encrypted blocks to recover their file tables.
+    pub(crate) fn decrypt_files<R: Read + Seek>(&mut self, archive: &mut BufReader<R>, keyring: &mut Keyring) -> Result<(), ArchiveError> {
+        let mut blk = Block::default();
+        for (offset, hdr) in self.offsets.iter().zip(self.blks.iter_mut()) {
+            if let Some(crypt) = &hdr.crypt {
+                let key = keyring.key(crypt)?;
+                archive.seek(SeekFrom::Start(*offset))?;
+                blk.read_from(archive)?;
+                blk.decrypt(&key)?;
+                hdr.files = std::mem::take(&mut blk.files);
+                blk.next();
+            }
+        }
+        Ok(())
+    }
+    /// Record the header and position of a block written to the archive.
+    pub(crate) fn push(&mut self, offset: u64, mut blk: Block) {
+        blk.data = Vec::new();
+        self.offsets.push(offset);
+        self.blks.push(blk);
+    }
+    /// Block headers and their positions in the archive.
+    pub(crate) fn blocks(&self) -> impl Iterator<Item = (u64, &Block)> {
+        self.offsets.iter().copied().zip(self.blks.iter())
+    }
     pub fn end_of_data(&self) -> u64 {
         self.eod
     }
+    pub fn set_end_of_data(&mut self, eod: u64) {
+        self.eod = eod;
+    }
     pub fn next_id(&mut self) -> u32 {
         self.next_id += 1;
         self.next_id - 1
@@ -66,4 +173,4 @@ impl fmt::Debug for ArchiveInfo {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,41 @@
+use std::{
+    fmt,
+    path::PathBuf,
+};
+
+/// Results of testing an archive: whether each block decompressed with
+/// a valid checksum, and whether each file was complete with a valid
+/// checksum. Files entirely within encrypted blocks that failed can't
+/// be listed, as their file tables can't be decrypted.
+#[derive(Default)]
+pub struct TestReport {
+    pub blocks:  Vec<(u32, bool)>,     // Block id and whether it passed
+    pub files:   Vec<(PathBuf, bool)>, // File path and whether it passed
+}
+impl TestReport {
+    /// Whether every block and file passed.
+    pub fn passed(&self) -> bool {
+        self.blocks.iter().all(|(_, ok)| *ok) &&
+        self.files.iter().all(|(_, ok)| *ok)
+    }
+}
+impl fmt::Display for TestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = |ok: bool| if ok { "OK" } else { "FAILED" };
+
+        writeln!(f, "Blocks:")?;
+        for (id, ok) in self.blocks.iter() {
+            writeln!(f, "    {id:<8}{}", status(*ok))?;
+        }
+        writeln!(f, "Files:")?;
+        for (path, ok) in self.files.iter() {
+            writeln!(f, "    {:<8}{}", status(*ok), path.display())?;
+        }
+        write!(f, "{} of {} blocks and {} of {} files passed.",
+            self.blocks.iter().filter(|(_, ok)| *ok).count(),
+            self.blocks.len(),
+            self.files.iter().filter(|(_, ok)| *ok).count(),
+            self.files.len(),
+        )
+    }
+}
@@ -1,16 +1,17 @@
 use std::{
-    fs::File,
-    io::{BufWriter, BufReader, Write},
+    io::{BufWriter, BufReader, Read, Write},
     path::PathBuf,
+    time::Duration,
     str,
     fmt,
 };
 use crate::{
-    filedata::FileData,
-    config::{Config, Method},
+    filedata::{FileData, Entry},
+    config::{Config, Method, Growth, Filter},
     buffered_io::{BufferedWrite, BufferedRead},
     error::ArchiveError,
-    constant::{MAGIC, Version},
+    constant::{MAGIC, MAX_MEM, Version},
+    crypto::{Crypt, BlockKey, TAG_LEN},
 };
 
 #[derive(Clone, Default)]
@@ -26,6 +27,9 @@ pub struct Block {
     pub ver:     Version,       // Version number
     pub data:    Vec<u8>,       // Block data 
     pub method:  Method,        // Context Mixing, LZW, or Uncompressed
+    pub growth:  Growth,        // LZW dictionary growth policy
+    pub filter:  Filter,        // Filter applied before compression
+    pub crypt:   Option<Crypt>, // Encryption parameters, if encrypted
 }
 impl Block {
     pub fn new(cfg: &Config) -> Block {
@@ -33,6 +37,9 @@ impl Block {
             mem:     cfg.mem,
             blk_sz:  cfg.blk_sz,
             method:  cfg.method,
+            growth:  cfg.growth,
+            filter:  cfg.filter.unwrap_or_default(),
+            crypt:   None,
             id:      0,
             chksum:  0,
             sizeo:   0,
@@ -43,25 +50,72 @@ impl Block {
             data:    Vec::with_capacity(cfg.blk_sz),
         }
     }
+    /// A block with no data and no files marks the end of an archive.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty() && self.files.is_empty()
+    }
     pub fn next(&mut self) {
         self.data.clear();
         self.files.clear();
         self.sizei = 0;
         self.id += 1;
     }
-    pub fn write_to(&self, archive: &mut BufWriter<File>) {
+    /// Write entire block
+    pub fn write_to<W: Write>(&self, archive: &mut BufWriter<W>) {
+        self.write_header_to(archive);
+
+        for byte in self.data.iter() {
+            archive.write_byte(*byte);
+        }
+    }
+    /// Write block header
+    pub fn write_header_to<W: Write>(&self, archive: &mut BufWriter<W>) {
+        self.write_fields_to(archive, self.id);
+
+        // File table of encrypted blocks is stored with the data
+        if self.crypt.is_some() {
+            archive.write_u32(0);
+        }
+        else {
+            self.write_files_to(archive);
+        }
+    }
+    /// Write fixed size header fields, with the given block id, in the 
+    /// layout of the block's version.
+    fn write_fields_to<W: Write>(&self, archive: &mut BufWriter<W>, id: u32) {
+        let ver = self.ver;
         archive.write_u32(MAGIC);
-        archive.write_u16(self.ver.major);
-        archive.write_u16(self.ver.minor);
-        archive.write_u16(self.ver.patch);
+        archive.write_u16(ver.major);
+        archive.write_u16(ver.minor);
+        archive.write_u16(ver.patch);
         archive.write_u64(self.mem);
         archive.write_u64(self.blk_sz as u64);
         archive.write_byte(self.method as u8);
-        archive.write_u32(self.id);
+        if ver.has(Version::GROWTH) {
+            archive.write_byte(self.growth as u8);
+        }
+        if ver.has(Version::FILTER) {
+            archive.write_byte(self.filter.id());
+        }
+        if ver.has(Version::PARAM) {
+            archive.write_u16(self.filter.param());
+        }
+        if ver.has(Version::CRYPT) {
+            match &self.crypt {
+                Some(crypt) => crypt.write_to(archive),
+                None => archive.write_byte(0),
+            }
+        }
+        archive.write_u32(id);
         archive.write_u32(self.chksum);
         archive.write_u64(self.sizeo);
         archive.write_u64(self.sizei);
         archive.write_u64(self.crtd);
+    }
+    /// Write number of files and file table, in the layout of the 
+    /// block's version.
+    fn write_files_to<W: Write>(&self, archive: &mut BufWriter<W>) {
+        let ver = self.ver;
         archive.write_u32(self.files.len() as u32);
 
         for file in self.files.iter() {
@@ -72,14 +126,31 @@ impl Block {
             archive.write_u64(file.seg_beg);
             archive.write_u64(file.seg_end);
             archive.write_u64(file.blk_pos);
-        }
-
-        for byte in self.data.iter() {
-            archive.write_byte(*byte);
+            if ver.has(Version::CHECKSUM) {
+                archive.write_u32(file.chksum);
+            }
+            if ver.has(Version::METADATA) {
+                archive.write_u32(file.mode);
+                archive.write_u32(file.uid);
+                archive.write_u32(file.gid);
+                archive.write_u64(file.atime.as_secs());
+                archive.write_u32(file.atime.subsec_nanos());
+                archive.write_u64(file.mtime.as_secs());
+                archive.write_u32(file.mtime.subsec_nanos());
+            }
+            if !ver.has(Version::ENTRY) {
+                continue;
+            }
+            archive.write_byte(file.entry.id());
+            if let Some(target) = file.entry.target() {
+                // Non UTF-8 targets are rejected when inputs are collected
+                archive.write_all(target.to_string_lossy().as_bytes()).unwrap();
+                archive.write_byte(0);
+            }
         }
     }
     /// Read entire block
-    pub fn read_from(&mut self, archive: &mut BufReader<File>) -> Result<(), ArchiveError>  {
+    pub fn read_from<R: Read>(&mut self, archive: &mut BufReader<R>) -> Result<(), ArchiveError>  {
         self.read_header_from(archive)?;
 
         self.data.reserve(self.blk_sz);
@@ -90,26 +161,101 @@ impl Block {
         }
         Ok(())
     }
-    /// Read block header
-    pub fn read_header_from(&mut self, archive: &mut BufReader<File>) -> Result<(), ArchiveError> {
+    /// Read block header, in the layout of the block's version.
+    pub fn read_header_from<R: Read>(&mut self, archive: &mut BufReader<R>) -> Result<(), ArchiveError> {
+        self.read_fields_from(archive)?;
+        self.read_files_from(archive)
+    }
+    /// Read a block that may be damaged, failing if its header isn't 
+    /// plausible for an archive with 'remaining' bytes left. Reading 
+    /// past the end of the archive must return zeros rather than fail.
+    pub fn read_salvaged_from<R: Read>(&mut self, archive: &mut BufReader<R>, remaining: u64) -> Result<(), ArchiveError> {
+        self.read_fields_from(archive)?;
+
+        // Smallest file table entry: an empty path and no link target
+        let min_file_size = FileData::default().size(self.ver) + 1;
+        let num_files = archive.read_u32();
+        if num_files as u64 > remaining / min_file_size {
+            return Err(ArchiveError::InvalidHeader(self.id));
+        }
+        self.files.clear();
+        self.read_file_table(archive, num_files)?;
+
+        // Memory usage is bounded when the fields are read. No real block
+        // is empty or larger than a terabyte.
+        let header = self.size() - self.data.len() as u64;
+        let plausible = header + self.sizeo <= remaining
+            && self.blk_sz > 0
+            && self.blk_sz as u64 <= 1 << 40
+            && (self.crypt.is_some() || self.sizei <= self.blk_sz as u64)
+            && self.files.iter().all(|file| {
+                file.seg_beg <= file.seg_end 
+                && file.seg_end <= file.len
+                && file.blk_pos + (file.seg_end - file.seg_beg) <= self.sizei
+            });
+        if !plausible {
+            return Err(ArchiveError::InvalidHeader(self.id));
+        }
+
+        self.data.clear();
+        archive.take(self.sizeo).read_to_end(&mut self.data)?;
+        Ok(())
+    }
+    /// Read fixed size header fields.
+    fn read_fields_from<R: Read>(&mut self, archive: &mut BufReader<R>) -> Result<(), ArchiveError> {
         let magic      = archive.read_u32();
         self.ver.major = archive.read_u16();
         self.ver.minor = archive.read_u16();
         self.ver.patch = archive.read_u16();
+
+        // Checked before reading the rest, which may be garbage
+        if magic != MAGIC { 
+            return Err(ArchiveError::InvalidMagicNumber(self.id));
+        }
+        // Header layout differs between versions
+        let ver = self.ver;
+        if !ver.readable() {
+            return Err(ArchiveError::InvalidVersion(ver));
+        }
+
         self.mem       = archive.read_u64();
         self.blk_sz    = archive.read_u64() as usize;
         self.method    = Method::from(archive.read_byte());
+        self.growth    = match ver.has(Version::GROWTH) {
+            true  => Growth::from(archive.read_byte()),
+            false => Growth::default(),
+        };
+        self.filter    = match (ver.has(Version::FILTER), ver.has(Version::PARAM)) {
+            (true, true)  => Filter::from((archive.read_byte(), archive.read_u16())),
+            (true, false) => Filter::from((archive.read_byte(), 0)),
+            _             => Filter::None,
+        };
+        self.crypt     = match ver.has(Version::CRYPT) {
+            true  => Crypt::read_from(archive, self.id)?,
+            false => None,
+        };
         self.id        = archive.read_u32();
         self.chksum    = archive.read_u32();
         self.sizeo     = archive.read_u64();
         self.sizei     = archive.read_u64();
         self.crtd      = archive.read_u64();
-        let num_files  = archive.read_u32();
 
-        if magic != MAGIC { 
-            return Err(ArchiveError::InvalidMagicNumber(self.id));
+        // Models are sized by mem, which a valid archive never sets above 
+        // the largest '-mem' option
+        if self.mem > MAX_MEM {
+            return Err(ArchiveError::InvalidHeader(self.id));
         }
-
+        Ok(())
+    }
+    /// Read number of files and file table.
+    fn read_files_from<R: Read>(&mut self, archive: &mut BufReader<R>) -> Result<(), ArchiveError> {
+        let num_files = archive.read_u32();
+        self.read_file_table(archive, num_files)
+    }
+    /// Read a file table of 'num_files' files, in the layout of the 
+    /// block's version.
+    fn read_file_table<R: Read>(&mut self, archive: &mut BufReader<R>, num_files: u32) -> Result<(), ArchiveError> {
+        let ver = self.ver;
         let mut path: Vec<u8> = Vec::with_capacity(64);
 
         // Read null terminated path strings and lengths
@@ -117,16 +263,43 @@ impl Block {
             loop {
                 match archive.read_byte() {
                     0 => {
-                        self.files.push(
-                            FileData {
-                                path:     PathBuf::from(str::from_utf8(&path)?),
-                                len:      archive.read_u64(), 
-                                seg_beg:  archive.read_u64(), 
-                                seg_end:  archive.read_u64(), 
-                                blk_pos:  archive.read_u64(), 
-                                ..Default::default() 
+                        let mut file = FileData {
+                            path:     PathBuf::from(str::from_utf8(&path)?),
+                            len:      archive.read_u64(), 
+                            seg_beg:  archive.read_u64(), 
+                            seg_end:  archive.read_u64(), 
+                            blk_pos:  archive.read_u64(), 
+                            ..Default::default() 
+                        };
+                        if ver.has(Version::CHECKSUM) {
+                            file.chksum = archive.read_u32();
+                        }
+                        if ver.has(Version::METADATA) {
+                            file.mode  = archive.read_u32();
+                            file.uid   = archive.read_u32();
+                            file.gid   = archive.read_u32();
+                            file.atime = read_time(archive, self.id)?;
+                            file.mtime = read_time(archive, self.id)?;
+                        }
+                        if !ver.has(Version::ENTRY) {
+                            self.files.push(file);
+                            path.clear();
+                            break;
+                        }
+                        let id = archive.read_byte();
+
+                        // Read null terminated target of symlink or hardlink
+                        path.clear();
+                        if id == 2 || id == 3 {
+                            loop {
+                                match archive.read_byte() {
+                                    0 => break,
+                                    byte => path.push(byte),
+                                }
                             }
-                        );
+                        }
+                        file.entry = Entry::from((id, PathBuf::from(str::from_utf8(&path)?)));
+                        self.files.push(file);
                         path.clear();
                         break;
                     }
@@ -136,19 +309,88 @@ impl Block {
         }
         Ok(())
     }
+    /// Encrypt file table and data, using the block id as the nonce.
+    pub fn encrypt(&mut self, key: &BlockKey, crypt: Crypt) {
+        let mut msg = BufWriter::new(Vec::new());
+        self.write_files_to(&mut msg);
+        msg.write_all(&self.data).unwrap();
+        let msg = msg.into_inner().unwrap();
+
+        self.crypt = Some(Crypt { nonce: self.id, ..crypt });
+        self.sizeo = (msg.len() + TAG_LEN) as u64;
+        self.data  = key.encrypt(self.id, &msg, &self.aad());
+    }
+    /// Decrypt file table and data, failing if the key is wrong or the
+    /// block has been modified.
+    pub fn decrypt(&mut self, key: &BlockKey) -> Result<(), ArchiveError> {
+        let nonce = match &self.crypt {
+            Some(crypt) => crypt.nonce,
+            None => return Ok(()),
+        };
+        let msg = key.decrypt(nonce, &self.data, &self.aad())
+            .ok_or(ArchiveError::DecryptionFailed(self.id))?;
+
+        let mut msg = BufReader::new(msg.as_slice());
+        self.files.clear();
+        self.read_files_from(&mut msg)?;
+        self.data.clear();
+        msg.read_to_end(&mut self.data)?;
+        self.sizeo = self.data.len() as u64;
+        self.crypt = None;
+        Ok(())
+    }
+    /// Header fields authenticated along with encrypted block contents.
+    /// The block id isn't included, as it changes when archives are merged.
+    fn aad(&self) -> Vec<u8> {
+        let mut aad = BufWriter::new(Vec::new());
+        self.write_fields_to(&mut aad, 0);
+        aad.into_inner().unwrap()
+    }
     pub fn size(&self) -> u64 {
-        self.files.iter().map(|file| file.size() + 1).sum::<u64>()
+        let ver = self.ver;
+        let (crypt, files) = match &self.crypt {
+            Some(crypt) => (crypt.size(), 0),
+            None => (0, self.files.iter().map(|file| file.size(ver) + 1).sum::<u64>()),
+        };
+        let fields = 
+            if ver.has(Version::GROWTH) { 1 } else { 0 } +
+            if ver.has(Version::FILTER) { 1 } else { 0 } +
+            if ver.has(Version::PARAM)  { 2 } else { 0 } +
+            if ver.has(Version::CRYPT)  { 1 } else { 0 };
+        files
+        + crypt
+        + fields
         + 63 
         + self.data.len() as u64
     }
 }
+
+/// Read a timestamp stored as seconds and nanoseconds, failing on 
+/// nanoseconds of a second or more rather than carrying them over.
+fn read_time<R: Read>(archive: &mut BufReader<R>, id: u32) -> Result<Duration, ArchiveError> {
+    let secs  = archive.read_u64();
+    let nanos = archive.read_u32();
+    if nanos >= 1_000_000_000 {
+        return Err(ArchiveError::InvalidHeader(id));
+    }
+    Ok(Duration::new(secs, nanos))
+}
+
 impl fmt::Display for Block {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // File table is only known once decrypted
+        if self.crypt.is_some() && self.files.is_empty() {
+            write!(f, "
+                \r(Block {} encrypted)", 
+                self.id
+            )?;
+        }
         for file in self.files.iter() {
-            if file.seg_beg != file.seg_end && file.seg_beg == 0 {
+            if file.seg_beg == 0 {
                 write!(f, "
-                    \r{}", 
-                    file.path.display()
+                    \r{}{}", 
+                    file.path.display(),
+                    file.entry
                 )?;
             }
         }
@@ -160,12 +402,18 @@ impl fmt::Debug for Block {
         write!(f, "
             \rBlock {}:
             \r==========================================
+            \rMethod:            {}
+            \rFilter:            {}
+            \rEncryption:        {}
             \rUncompressed Size: {}
             \rCompressed Size:   {}
             \rCRC32 Checksum:    {:x}
             \rCreation time:     {}
             \rMemory Usage:      {}\n\n",
             self.id,
+            self.method,
+            self.filter,
+            self.crypt.as_ref().map_or(String::from("None"), |crypt| crypt.to_string()),
             self.sizei, 
             self.sizeo,
             self.chksum, 
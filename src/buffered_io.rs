@@ -10,6 +10,7 @@ use std::{
 use crate::{
     filedata::FileData,
     error::ArchiveError,
+    volume::Volumes,
 };
 
 // Indicates an empty or non-empty buffer.
@@ -27,7 +28,7 @@ pub trait BufferedRead {
     fn read_u64(&mut self) -> u64;
     fn fill_buffer(&mut self) -> BufferState;
 }
-impl BufferedRead for BufReader<File> {
+impl<R: Read> BufferedRead for BufReader<R> {
     /// Read one byte from an input file.
     fn read_byte(&mut self) -> u8 {
         let mut byte = [0u8; 1];
@@ -130,7 +131,7 @@ pub trait BufferedWrite {
     fn write_u64(&mut self, output: u64);
     fn flush_buffer(&mut self);
 }
-impl BufferedWrite for BufWriter<File> {
+impl<W: Write> BufferedWrite for BufWriter<W> {
     /// Write one byte to an output file.
     fn write_byte(&mut self, output: u8) {
         if let Err(e) = self.write(&[output]) {
@@ -194,22 +195,24 @@ pub fn new_input_file(path: &Path) -> Result<BufReader<File>, ArchiveError> {
         Err(err) => {
             match err.kind() {
                 ErrorKind::PermissionDenied => {
-                    return Err(
+                    Err(
                         ArchiveError::FileAccessDenied(path.to_path_buf())
-                    );
+                    )
                 }
                 _ => {
-                    return Err(
+                    Err(
                         ArchiveError::IoError(err)
-                    );
+                    )
                 }
             }
         }
     }
 }
 
+/// Opens an output file, or the file being extracted if 'file' is a
+/// later segment of it.
 pub fn new_output_file(file: &FileData, clobber: bool) -> Result<BufWriter<File>, ArchiveError> {
-    if !file.path.exists() || file.len == 0 || clobber {
+    if !file.path.exists() || file.len == 0 || file.seg_beg > 0 || clobber {
         match OpenOptions::new()
             .write(true)
             .truncate(file.seg_beg == 0)
@@ -221,14 +224,14 @@ pub fn new_output_file(file: &FileData, clobber: bool) -> Result<BufWriter<File>
                 Err(err) => {
                     match err.kind() {
                         ErrorKind::PermissionDenied => {
-                            return Err(
+                            Err(
                                 ArchiveError::FileAccessDenied(file.path.clone())
-                            );
+                            )
                         }
                         _ => {
-                            return Err(
+                            Err(
                                 ArchiveError::IoError(err)
-                            );
+                            )
                         }
                     }
                 }
@@ -240,12 +243,22 @@ pub fn new_output_file(file: &FileData, clobber: bool) -> Result<BufWriter<File>
     
 }
 
+/// Takes an archive path and returns the archive, either a single file 
+/// or a volume set, wrapped in a BufReader.
+pub fn new_input_archive(path: &Path) -> Result<BufReader<Volumes>, ArchiveError> {
+    Ok(BufReader::with_capacity(4096, Volumes::open(path)?))
+}
+
+/// Opens a new or existing archive for writing, split into volumes of 
+/// 'size' bytes if given, wrapped in a BufWriter.
+pub fn new_output_archive(arch: &FileData, size: Option<u64>, clobber: bool) -> Result<BufWriter<Volumes>, ArchiveError> {
+    Ok(BufWriter::with_capacity(4096, Volumes::create(arch, size, clobber)?))
+}
+
 /// Create a new directory if it doesn't already exist.
 pub fn new_dir(out: &FileData) -> io::Result<()> {
     if !out.path.exists() {
-        if let Err(err) = create_dir(&out.path) {
-            return Err(err);
-        }
+        create_dir(&out.path)?;
     }
     Ok(())
 }
@@ -0,0 +1,5 @@
+pub mod encoder;
+pub mod decoder;
+mod suffix;
+mod mtf;
+mod symbol;
@@ -0,0 +1,75 @@
+use crate::{
+    cm::coder::Decoder,
+    bwt::{
+        mtf,
+        symbol::SymbolModel,
+    },
+};
+
+/// Inverse Burrows-Wheeler transform. Each row of the sorted rotations 
+/// is mapped to the row beginning with its last byte (the LF mapping), 
+/// and the data is rebuilt backwards starting from the row containing 
+/// only the end of data symbol.
+fn inverse(bwt: &[u8], primary: usize) -> Vec<u8> {
+    let n = bwt.len();
+    if primary == 0 || primary > n {
+        return Vec::new();
+    }
+    // Byte in the last column of each row, None for end of data.
+    let last = |row: usize| -> Option<u8> {
+        match row {
+            r if r == primary => None,
+            r if r < primary  => Some(bwt[r]),
+            r => Some(bwt[r - 1]),
+        }
+    };
+
+    // First row of each byte's bucket, after the end of data row.
+    let mut counts = [0usize; 256];
+    for &b in bwt.iter() {
+        counts[b as usize] += 1;
+    }
+    let mut next = [0usize; 256];
+    let mut sum = 1;
+    for (n, c) in next.iter_mut().zip(counts.iter()) {
+        *n = sum;
+        sum += c;
+    }
+
+    let mut lf = vec![0usize; n + 1];
+    for (row, l) in lf.iter_mut().enumerate() {
+        if let Some(b) = last(row) {
+            *l = next[b as usize];
+            next[b as usize] += 1;
+        }
+    }
+
+    let mut data = vec![0u8; n];
+    let mut row = 0;
+    for byte in data.iter_mut().rev() {
+        *byte = last(row).unwrap_or(0);
+        row = lf[row];
+    }
+    data
+}
+
+/// Decompress a block compressed with BWT. Corrupted data can claim any 
+/// length, so blocks claiming more than 'limit' bytes aren't decoded.
+pub fn decompress(blk_in: Vec<u8>, limit: usize) -> Vec<u8> {
+    if blk_in.len() < 16 {
+        return Vec::new();
+    }
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&blk_in[..8]);
+    let len = u64::from_le_bytes(bytes) as usize;
+    bytes.copy_from_slice(&blk_in[8..16]);
+    let primary = u64::from_le_bytes(bytes) as usize;
+    if len > limit {
+        return Vec::new();
+    }
+
+    let mut model = SymbolModel::new();
+    let mut dec = Decoder::new(blk_in[16..].to_vec());
+    let bwt = mtf::decode(len, || model.decode(&mut dec));
+    inverse(&bwt, primary)
+}
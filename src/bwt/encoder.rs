@@ -0,0 +1,54 @@
+use crate::{
+    cm::coder::Encoder,
+    bwt::{
+        suffix::suffix_array,
+        mtf,
+        symbol::SymbolModel,
+    },
+};
+
+/// Burrows-Wheeler transform. Returns the last column of the sorted 
+/// rotations of data followed by a unique end of data symbol, without 
+/// that symbol, and the row in which it appeared.
+fn transform(data: &[u8]) -> (Vec<u8>, usize) {
+    let s = data.iter().map(|&b| b as u32).collect::<Vec<u32>>();
+    let sa = suffix_array(&s, 256);
+
+    // The first row is the end of data symbol alone, preceded by the 
+    // last byte.
+    let mut bwt = Vec::with_capacity(data.len());
+    bwt.push(data[data.len() - 1]);
+    let mut primary = 0;
+
+    for (row, &i) in sa.iter().enumerate() {
+        if i == 0 {
+            primary = row + 1;
+        }
+        else {
+            bwt.push(data[i - 1]);
+        }
+    }
+    (bwt, primary)
+}
+
+/// Compress a block using a Burrows-Wheeler transform, move-to-front
+/// transform and zero run length coding, followed by arithmetic coding.
+/// The uncompressed length and BWT primary index are stored first.
+pub fn compress(blk_in: Vec<u8>) -> Vec<u8> {
+    if blk_in.is_empty() {
+        return Vec::new();
+    }
+    let (bwt, primary) = transform(&blk_in);
+    let symbols = mtf::encode(&bwt);
+
+    let mut enc = Encoder::new();
+    enc.out.extend_from_slice(&(blk_in.len() as u64).to_le_bytes());
+    enc.out.extend_from_slice(&(primary as u64).to_le_bytes());
+
+    let mut model = SymbolModel::new();
+    for &symbol in symbols.iter() {
+        model.encode(&mut enc, symbol);
+    }
+    enc.flush();
+    enc.out
+}
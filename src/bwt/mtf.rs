@@ -0,0 +1,83 @@
+// Move-to-front transform followed by zero run length coding. After a
+// BWT, runs of repeated bytes become runs of zeros, which are replaced
+// by their length written in bijective base 2 with digits RUNA (1) and 
+// RUNB (2). A non-zero rank r is written as r + 1.
+
+pub const RUNA: u16 = 0;
+pub const RUNB: u16 = 1;
+
+/// Transform bytes into symbols in the range 0..=256.
+pub fn encode(data: &[u8]) -> Vec<u16> {
+    let mut order = [0u8; 256];
+    for (i, o) in order.iter_mut().enumerate() {
+        *o = i as u8;
+    }
+    let mut symbols = Vec::with_capacity(data.len() / 2);
+    let mut run = 0;
+
+    for &byte in data.iter() {
+        let rank = order.iter().position(|&o| o == byte).unwrap();
+        order.copy_within(0..rank, 1);
+        order[0] = byte;
+
+        if rank == 0 {
+            run += 1;
+        }
+        else {
+            write_run(&mut symbols, run);
+            run = 0;
+            symbols.push(rank as u16 + 1);
+        }
+    }
+    write_run(&mut symbols, run);
+    symbols
+}
+
+fn write_run(symbols: &mut Vec<u16>, mut run: usize) {
+    while run > 0 {
+        if run & 1 == 1 {
+            symbols.push(RUNA);
+            run = (run - 1) >> 1;
+        }
+        else {
+            symbols.push(RUNB);
+            run = (run - 2) >> 1;
+        }
+    }
+}
+
+/// Inverse of encode(), producing len bytes. Symbols are supplied by a 
+/// closure so they can be decoded as needed.
+pub fn decode(len: usize, mut next: impl FnMut() -> u16) -> Vec<u8> {
+    let mut order = [0u8; 256];
+    for (i, o) in order.iter_mut().enumerate() {
+        *o = i as u8;
+    }
+    let mut data = Vec::with_capacity(len);
+    let mut run = 0;
+    let mut weight = 1;
+
+    while data.len() < len {
+        let symbol = next();
+        if symbol == RUNA || symbol == RUNB {
+            run += weight * (symbol as usize + 1);
+            weight <<= 1;
+            // Truncated or corrupted data, leave it to the checksum.
+            if run > len {
+                break;
+            }
+            continue;
+        }
+        data.resize(data.len() + run, order[0]);
+        run = 0;
+        weight = 1;
+
+        let rank = (symbol - 1) as usize;
+        let byte = order[rank];
+        order.copy_within(0..rank, 1);
+        order[0] = byte;
+        data.push(byte);
+    }
+    data.resize(len, order[0]);
+    data
+}
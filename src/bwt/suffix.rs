@@ -0,0 +1,151 @@
+// Suffix array construction by induced sorting (SA-IS).
+//
+// Suffixes are classified as S-type (smaller than the next suffix) or
+// L-type (larger). The leftmost S-type suffixes of each run (LMS suffixes)
+// are sorted first, recursively if necessary, and the order of all other
+// suffixes is induced from them in two linear passes. The string is 
+// treated as if followed by a unique sentinel smaller than every symbol.
+
+const EMPTY: usize = usize::MAX;
+
+/// Return the suffix array of s, where every symbol is less than k.
+pub fn suffix_array(s: &[u32], k: usize) -> Vec<usize> {
+    let n = s.len();
+    let mut sa = vec![EMPTY; n];
+    if n == 0 {
+        return sa;
+    }
+    if n == 1 {
+        sa[0] = 0;
+        return sa;
+    }
+
+    // Classify suffixes, true = S-type.
+    let mut stype = vec![false; n];
+    for i in (0..n - 1).rev() {
+        stype[i] = s[i] < s[i+1] || (s[i] == s[i+1] && stype[i+1]);
+    }
+    let is_lms = |i: usize| i == n || (i > 0 && stype[i] && !stype[i-1]);
+
+    let mut counts = vec![0usize; k];
+    for &c in s.iter() {
+        counts[c as usize] += 1;
+    }
+
+    // Sort LMS substrings by placing LMS suffixes at the ends of their 
+    // buckets and inducing.
+    let lms = (1..n).filter(|&i| is_lms(i)).collect::<Vec<usize>>();
+    let mut tails = bucket_tails(&counts);
+    for &i in lms.iter().rev() {
+        let c = s[i] as usize;
+        tails[c] -= 1;
+        sa[tails[c]] = i;
+    }
+    induce(s, &stype, &counts, &mut sa);
+
+    // Name LMS substrings in sorted order, giving equal substrings
+    // equal names.
+    let sorted = sa.iter().copied().filter(|&i| is_lms(i)).collect::<Vec<usize>>();
+    let mut names = vec![EMPTY; n];
+    let mut name = 0;
+    let mut prev = EMPTY;
+    for &i in sorted.iter() {
+        if prev != EMPTY && !lms_eq(s, &stype, prev, i) {
+            name += 1;
+        }
+        names[i] = name;
+        prev = i;
+    }
+    let num_names = if sorted.is_empty() { 0 } else { name + 1 };
+
+    // Sort LMS suffixes, recursing if any names are repeated.
+    let reduced = lms.iter().map(|&i| names[i] as u32).collect::<Vec<u32>>();
+    let order = if num_names < reduced.len() {
+        suffix_array(&reduced, num_names)
+    }
+    else {
+        let mut order = vec![0; reduced.len()];
+        for (i, &r) in reduced.iter().enumerate() {
+            order[r as usize] = i;
+        }
+        order
+    };
+
+    // Induce final order from sorted LMS suffixes.
+    sa.iter_mut().for_each(|i| *i = EMPTY);
+    let mut tails = bucket_tails(&counts);
+    for &r in order.iter().rev() {
+        let i = lms[r];
+        let c = s[i] as usize;
+        tails[c] -= 1;
+        sa[tails[c]] = i;
+    }
+    induce(s, &stype, &counts, &mut sa);
+    sa
+}
+
+fn bucket_heads(counts: &[usize]) -> Vec<usize> {
+    let mut sum = 0;
+    counts.iter().map(|&c| { sum += c; sum - c }).collect()
+}
+
+fn bucket_tails(counts: &[usize]) -> Vec<usize> {
+    let mut sum = 0;
+    counts.iter().map(|&c| { sum += c; sum }).collect()
+}
+
+/// Induce L-type suffixes from left to right, then S-type suffixes from
+/// right to left.
+fn induce(s: &[u32], stype: &[bool], counts: &[usize], sa: &mut [usize]) {
+    let n = s.len();
+
+    // The last suffix is L-type and is preceded only by the sentinel.
+    let mut heads = bucket_heads(counts);
+    let c = s[n-1] as usize;
+    sa[heads[c]] = n - 1;
+    heads[c] += 1;
+
+    for i in 0..n {
+        let j = sa[i];
+        if j != EMPTY && j > 0 && !stype[j-1] {
+            let c = s[j-1] as usize;
+            sa[heads[c]] = j - 1;
+            heads[c] += 1;
+        }
+    }
+
+    let mut tails = bucket_tails(counts);
+    for i in (0..n).rev() {
+        let j = sa[i];
+        if j != EMPTY && j > 0 && stype[j-1] {
+            let c = s[j-1] as usize;
+            tails[c] -= 1;
+            sa[tails[c]] = j - 1;
+        }
+    }
+}
+
+/// Check if the LMS substrings starting at a and b are equal.
+fn lms_eq(s: &[u32], stype: &[bool], a: usize, b: usize) -> bool {
+    let n = s.len();
+    let is_lms = |i: usize| i == n || (i > 0 && stype[i] && !stype[i-1]);
+    let mut i = 0;
+    loop {
+        if a + i == n || b + i == n {
+            return false;
+        }
+        if s[a+i] != s[b+i] || stype[a+i] != stype[b+i] {
+            return false;
+        }
+        if i > 0 {
+            let (la, lb) = (is_lms(a + i), is_lms(b + i));
+            if la && lb {
+                return true;
+            }
+            if la != lb {
+                return false;
+            }
+        }
+        i += 1;
+    }
+}
@@ -0,0 +1,70 @@
+use crate::{
+    cm::coder::{Encoder, Decoder, Model},
+    bwt::mtf::{RUNA, RUNB},
+};
+
+/// Adaptive models for coding MTF symbols. A symbol is coded as a flag
+/// indicating a zero run digit, then either the digit, or the rank's
+/// bit length followed by the bits below its leading 1. All models are 
+/// selected by the class of the previous symbol, which captures whether
+/// the data is currently predictable.
+pub struct SymbolModel {
+    run:    [Model; 4],     // Symbol is a run digit
+    digit:  [Model; 4],     // RUNA or RUNB
+    slot:   Vec<Model>,     // Bit length of rank
+    low:    Vec<Model>,     // Bits below leading 1, per slot
+    cxt:    usize,          // Class of previous symbol
+}
+impl SymbolModel {
+    pub fn new() -> SymbolModel {
+        SymbolModel {
+            run:    [Model::default(); 4],
+            digit:  [Model::default(); 4],
+            slot:   vec![Model::default(); 4 * 8],
+            low:    vec![Model::default(); 4 * 8 * 128],
+            cxt:    0,
+        }
+    }
+
+    /// Class of a symbol: run digit, rank 1, rank 2..3, or higher.
+    fn class(symbol: u16) -> usize {
+        match symbol {
+            RUNA | RUNB => 0,
+            2     => 1,
+            3..=4 => 2,
+            _     => 3,
+        }
+    }
+
+    pub fn encode(&mut self, enc: &mut Encoder, symbol: u16) {
+        let cxt = self.cxt;
+        if symbol == RUNA || symbol == RUNB {
+            enc.encode_model(1, &mut self.run[cxt]);
+            enc.encode_model(symbol as i32, &mut self.digit[cxt]);
+        }
+        else {
+            enc.encode_model(0, &mut self.run[cxt]);
+            let rank = (symbol - 1) as u32;
+            let slot = 31 - rank.leading_zeros();
+            enc.encode_tree(&mut self.slot[cxt * 8..], slot, 3);
+            let low = &mut self.low[(cxt * 8 + slot as usize) * 128..];
+            enc.encode_tree(low, rank & ((1 << slot) - 1), slot);
+        }
+        self.cxt = SymbolModel::class(symbol);
+    }
+
+    pub fn decode(&mut self, dec: &mut Decoder) -> u16 {
+        let cxt = self.cxt;
+        let symbol = if dec.decode_model(&mut self.run[cxt]) == 1 {
+            dec.decode_model(&mut self.digit[cxt]) as u16
+        }
+        else {
+            let slot = dec.decode_tree(&mut self.slot[cxt * 8..], 3);
+            let low = &mut self.low[(cxt * 8 + slot as usize) * 128..];
+            let rank = (1 << slot) | dec.decode_tree(low, slot);
+            rank as u16 + 1
+        };
+        self.cxt = SymbolModel::class(symbol);
+        symbol
+    }
+}
@@ -0,0 +1,9 @@
+pub mod encoder;
+pub mod decoder;
+pub mod coder;
+mod predictor;
+mod logistic;
+mod statemap;
+mod mixer;
+mod apm;
+mod matchmodel;
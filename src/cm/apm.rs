@@ -0,0 +1,42 @@
+use crate::cm::logistic::{stretch, squash};
+
+/// An Adaptive Probability Map refines a probability by mapping it, along
+/// with a context, to a new probability. The input probability is stretched
+/// and quantized to one of 33 buckets, and the output is interpolated
+/// between the two nearest buckets. Only the nearer bucket is updated.
+pub struct Apm {
+    t:     Vec<u16>, // Context * 33 buckets -> probability
+    idx:   usize,    // Index of last bucket used
+    rate:  i32,      // Update rate
+}
+impl Apm {
+    pub fn new(n: usize, rate: i32) -> Apm {
+        let mut t = vec![0u16; n * 33];
+        for i in 0..n {
+            for j in 0..33 {
+                t[i * 33 + j] = (squash((j as i32 - 16) * 128) * 16) as u16;
+            }
+        }
+        Apm {
+            t,
+            idx:  0,
+            rate,
+        }
+    }
+
+    /// Refine probability pr given context cxt.
+    pub fn p(&mut self, pr: i32, cxt: usize) -> i32 {
+        let s = stretch(pr) + 2048;
+        let lo = (s & 127) as u32;
+        let i = (s >> 7) as usize + cxt * 33;
+        self.idx = i + ((lo >> 6) as usize);
+        ((self.t[i] as u32 * (128 - lo) + self.t[i+1] as u32 * lo) >> 11) as i32
+    }
+
+    /// Move the bucket nearest to the last prediction towards bit.
+    pub fn update(&mut self, bit: i32) {
+        let g = (bit << 16) + (bit << self.rate) - bit - bit;
+        let t = self.t[self.idx] as i32;
+        self.t[self.idx] = (t + ((g - t) >> self.rate)) as u16;
+    }
+}
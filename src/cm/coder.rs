@@ -0,0 +1,187 @@
+/// Binary arithmetic coder. Bits are coded with a 12 bit probability 
+/// that the bit is a 1, narrowing the range [x1, x2] accordingly. 
+/// Leading bytes are output once they are shared by x1 and x2.
+pub struct Encoder {
+    x1:       u32,     // Low end of range
+    x2:       u32,     // High end of range
+    pub out:  Vec<u8>, // Compressed data
+}
+impl Encoder {
+    pub fn new() -> Encoder {
+        Encoder {
+            x1:   0,
+            x2:   0xFFFFFFFF,
+            out:  Vec::new(),
+        }
+    }
+
+    /// Code one bit with 12 bit probability p.
+    pub fn encode(&mut self, bit: i32, p: i32) {
+        let xmid = self.x1 + ((self.x2 - self.x1) >> 12) * p as u32;
+
+        if bit == 1 {
+            self.x2 = xmid;
+        }
+        else {
+            self.x1 = xmid + 1;
+        }
+
+        while (self.x1 ^ self.x2) & 0xFF000000 == 0 {
+            self.out.push((self.x2 >> 24) as u8);
+            self.x1 <<= 8;
+            self.x2 = (self.x2 << 8) | 255;
+        }
+    }
+
+    /// Output enough bytes of x1 to disambiguate the final range.
+    pub fn flush(&mut self) {
+        self.out.extend_from_slice(&self.x1.to_be_bytes());
+    }
+}
+impl Default for Encoder {
+    fn default() -> Encoder {
+        Encoder::new()
+    }
+}
+
+/// Binary arithmetic decoder, the inverse of Encoder.
+pub struct Decoder {
+    x1:      u32,     // Low end of range
+    x2:      u32,     // High end of range
+    x:       u32,     // Last 4 bytes of compressed data
+    stream:  Vec<u8>, // Compressed data
+    pos:     usize,   // Position in compressed data
+}
+impl Decoder {
+    pub fn new(stream: Vec<u8>) -> Decoder {
+        let mut dec = Decoder {
+            x1:      0,
+            x2:      0xFFFFFFFF,
+            x:       0,
+            stream,
+            pos:     0,
+        };
+        for _ in 0..4 {
+            dec.x = (dec.x << 8) | dec.next_byte();
+        }
+        dec
+    }
+
+    fn next_byte(&mut self) -> u32 {
+        let byte = self.stream.get(self.pos).copied().unwrap_or(0);
+        self.pos += 1;
+        byte as u32
+    }
+
+    /// Whether decoding has gone past the end of the compressed data,
+    /// which only happens when the data is corrupted.
+    pub fn exhausted(&self) -> bool {
+        self.pos > self.stream.len() + 4
+    }
+
+    /// Decode one bit with 12 bit probability p.
+    pub fn decode(&mut self, p: i32) -> i32 {
+        let xmid = self.x1 + ((self.x2 - self.x1) >> 12) * p as u32;
+
+        let bit = if self.x <= xmid {
+            self.x2 = xmid;
+            1
+        }
+        else {
+            self.x1 = xmid + 1;
+            0
+        };
+
+        while (self.x1 ^ self.x2) & 0xFF000000 == 0 {
+            self.x1 <<= 8;
+            self.x2 = (self.x2 << 8) | 255;
+            self.x = (self.x << 8) | self.next_byte();
+        }
+        bit
+    }
+}
+
+/// An adaptive probability that a binary decision is a 1, for coding 
+/// symbols with Encoder and Decoder outside of the context mixing 
+/// Predictor.
+#[derive(Clone, Copy)]
+pub struct Model {
+    p: u16, // 16 bit probability
+}
+impl Model {
+    /// Return a 12 bit probability that the next bit is a 1.
+    pub fn p(&self) -> i32 {
+        ((self.p >> 4) as i32).clamp(1, 4095)
+    }
+
+    /// Move probability towards bit.
+    pub fn update(&mut self, bit: i32) {
+        if bit == 1 {
+            self.p += (65535 - self.p) >> 4;
+        }
+        else {
+            self.p -= self.p >> 4;
+        }
+    }
+}
+impl Default for Model {
+    fn default() -> Model {
+        Model { 
+            p: 1 << 15 
+        }
+    }
+}
+
+impl Encoder {
+    /// Code one bit with an adaptive model.
+    pub fn encode_model(&mut self, bit: i32, model: &mut Model) {
+        self.encode(bit, model.p());
+        model.update(bit);
+    }
+
+    /// Code the low n bits of value, most significant first, using a 
+    /// binary tree of 1 << n models.
+    pub fn encode_tree(&mut self, models: &mut [Model], value: u32, n: u32) {
+        let mut node = 1;
+        for i in (0..n).rev() {
+            let bit = ((value >> i) & 1) as i32;
+            self.encode_model(bit, &mut models[node]);
+            node = (node << 1) | bit as usize;
+        }
+    }
+
+    /// Code the low n bits of value, most significant first, with no model.
+    pub fn encode_direct(&mut self, value: u32, n: u32) {
+        for i in (0..n).rev() {
+            self.encode(((value >> i) & 1) as i32, 2048);
+        }
+    }
+}
+
+impl Decoder {
+    /// Decode one bit with an adaptive model.
+    pub fn decode_model(&mut self, model: &mut Model) -> i32 {
+        let bit = self.decode(model.p());
+        model.update(bit);
+        bit
+    }
+
+    /// Decode n bits using a binary tree of 1 << n models.
+    pub fn decode_tree(&mut self, models: &mut [Model], n: u32) -> u32 {
+        let mut node = 1;
+        for _ in 0..n {
+            let bit = self.decode_model(&mut models[node]);
+            node = (node << 1) | bit as usize;
+        }
+        (node - (1 << n)) as u32
+    }
+
+    /// Decode n bits coded with no model.
+    pub fn decode_direct(&mut self, n: u32) -> u32 {
+        let mut value = 0;
+        for _ in 0..n {
+            value = (value << 1) | self.decode(2048) as u32;
+        }
+        value
+    }
+}
@@ -0,0 +1,33 @@
+use crate::cm::{
+    predictor::Predictor,
+    coder::Decoder,
+};
+
+/// Decompress a block compressed with context mixing. Corrupted data can
+/// claim any length, so blocks claiming more than 'limit' bytes aren't decoded.
+pub fn decompress(blk_in: Vec<u8>, mem: usize, limit: usize) -> Vec<u8> {
+    if blk_in.len() < 8 {
+        return Vec::new();
+    }
+    let mut len = [0u8; 8];
+    len.copy_from_slice(&blk_in[..8]);
+    let len = u64::from_le_bytes(len) as usize;
+    if len > limit {
+        return Vec::new();
+    }
+
+    let mut pred = Predictor::new(mem);
+    let mut dec = Decoder::new(blk_in[8..].to_vec());
+    let mut blk = Vec::with_capacity(len);
+
+    for _ in 0..len {
+        let mut byte = 0;
+        for _ in 0..8 {
+            let bit = dec.decode(pred.p());
+            pred.update(bit);
+            byte = (byte << 1) | bit as u8;
+        }
+        blk.push(byte);
+    }
+    blk
+}
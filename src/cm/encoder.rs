@@ -0,0 +1,25 @@
+use crate::cm::{
+    predictor::Predictor,
+    coder::Encoder,
+};
+
+/// Compress a block using context mixing. The uncompressed length is 
+/// stored first so the decoder knows when to stop.
+pub fn compress(blk_in: Vec<u8>, mem: usize) -> Vec<u8> {
+    if blk_in.is_empty() {
+        return Vec::new();
+    }
+    let mut pred = Predictor::new(mem);
+    let mut enc = Encoder::new();
+    enc.out.extend_from_slice(&(blk_in.len() as u64).to_le_bytes());
+
+    for byte in blk_in.iter() {
+        for i in (0..8).rev() {
+            let bit = ((*byte >> i) & 1) as i32;
+            enc.encode(bit, pred.p());
+            pred.update(bit);
+        }
+    }
+    enc.flush();
+    enc.out
+}
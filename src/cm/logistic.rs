@@ -0,0 +1,48 @@
+// Logistic functions used to convert between the probability domain
+// (12 bit, 0..4095) and the logistic or "stretched" domain (-2047..2047).
+
+/// Interpolation table for squash().
+const SQUASH: [i32; 33] = [
+       1,    2,    3,    6,   10,   16,   27,   45,   73,  120,  194,
+     310,  488,  747, 1101, 1546, 2047, 2549, 2994, 3348, 3607, 3785,
+    3901, 3975, 4024, 4050, 4068, 4079, 4085, 4089, 4092, 4093, 4094,
+];
+
+/// Inverse of stretch(), returns p = 4096/(1 + e^-d).
+pub const fn squash(d: i32) -> i32 {
+    if d > 2047  { return 4095; }
+    if d < -2047 { return 0;    }
+    let w = d & 127;
+    let i = ((d >> 7) + 16) as usize;
+    (SQUASH[i] * (128 - w) + SQUASH[i+1] * w + 64) >> 7
+}
+
+/// Pre-computed stretch table, the inverse of squash().
+const STRETCH: [i16; 4096] = stretch_table();
+
+const fn stretch_table() -> [i16; 4096] {
+    let mut table = [0i16; 4096];
+    let mut pi = 0;
+    let mut x = -2047;
+    while x <= 2047 {
+        let v = squash(x);
+        let mut i = pi;
+        while i <= v {
+            table[i as usize] = x as i16;
+            i += 1;
+        }
+        pi = v + 1;
+        x += 1;
+    }
+    let mut i = pi;
+    while i < 4096 {
+        table[i as usize] = 2047;
+        i += 1;
+    }
+    table
+}
+
+/// Returns ln(p/(1-p)), with p scaled to 12 bits and output scaled by 256.
+pub fn stretch(p: i32) -> i32 {
+    STRETCH[p as usize] as i32
+}
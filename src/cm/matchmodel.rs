@@ -0,0 +1,103 @@
+use crate::cm::{
+    statemap::StateMap,
+    logistic::stretch,
+};
+
+const MIN_LEN: usize = 6;  // Minimum match length
+const MAX_LEN: usize = 62; // Maximum tracked match length
+
+/// A MatchModel finds the most recent occurrence of the last MIN_LEN bytes
+/// and predicts that the next bit will be the bit that followed it. The 
+/// prediction is adjusted by a StateMap with the match length and 
+/// expected bit as context.
+pub struct MatchModel {
+    buf:      Vec<u8>,   // History
+    ht:       Vec<u32>,  // Hash of last MIN_LEN bytes -> position in buf
+    pos:      usize,     // Number of bytes in history
+    ptr:      usize,     // Position of predicted byte in buf
+    len:      usize,     // Length of current match
+    hash:     usize,     // Hash of last MIN_LEN bytes
+    exp_bit:  i32,       // Expected bit
+    sm:       StateMap,  // Match length and expected bit -> probability
+}
+impl MatchModel {
+    pub fn new(mem: usize) -> MatchModel {
+        MatchModel {
+            buf:      vec![0; mem / 4],
+            ht:       vec![0; mem / 16],
+            pos:      0,
+            ptr:      0,
+            len:      0,
+            hash:     0,
+            exp_bit:  0,
+            sm:       StateMap::new(MAX_LEN * 2 + 2, 1023),
+        }
+    }
+
+    /// Add two predictions to the mixer inputs, one based on the length 
+    /// of the current match and one only on whether a match exists.
+    pub fn p(&mut self, c0: u32, bit_pos: u32, inputs: &mut [i32; 2]) {
+        if self.len > 0 {
+            let pb = self.buf[self.ptr & (self.buf.len() - 1)] as u32 + 256;
+            // Expected byte must agree with bits seen so far.
+            if (pb >> (8 - bit_pos)) == c0 {
+                self.exp_bit = ((pb >> (7 - bit_pos)) & 1) as i32;
+            }
+            else {
+                self.len = 0;
+            }
+        }
+        let cxt = if self.len > 0 {
+            self.len.min(MAX_LEN) * 2 + self.exp_bit as usize
+        }
+        else {
+            0
+        };
+        let st = stretch(self.sm.p(cxt));
+        inputs[0] = st;
+        inputs[1] = if self.len > 0 {
+            if self.exp_bit == 1 { 256 } else { -256 }
+        }
+        else { 
+            0 
+        };
+    }
+
+    pub fn update(&mut self, bit: i32) {
+        self.sm.update(bit);
+    }
+
+    /// Add a byte to history and find or extend a match.
+    pub fn update_byte(&mut self, byte: u8) {
+        let mask = self.buf.len() - 1;
+
+        if self.len > 0 && self.buf[self.ptr & mask] == byte {
+            self.len += 1;
+            self.ptr += 1;
+        }
+        else {
+            self.len = 0;
+        }
+
+        self.buf[self.pos & mask] = byte;
+        self.pos += 1;
+        self.hash = (self.hash * (3 << 3) + byte as usize + 1) & (self.ht.len() - 1);
+
+        if self.len == 0 && self.pos >= MIN_LEN {
+            let ptr = self.ht[self.hash] as usize;
+            if ptr > 0 && self.pos - ptr < self.buf.len() {
+                let mut len = 0;
+                while len < MAX_LEN 
+                && len < ptr 
+                && self.buf[(ptr - len - 1) & mask] == self.buf[(self.pos - len - 1) & mask] {
+                    len += 1;
+                }
+                if len >= MIN_LEN {
+                    self.len = len;
+                    self.ptr = ptr;
+                }
+            }
+        }
+        self.ht[self.hash] = self.pos as u32;
+    }
+}
@@ -0,0 +1,56 @@
+use crate::cm::logistic::squash;
+
+/// A Mixer combines a set of predictions in the logistic domain using a
+/// single layer neural network. One of several weight sets is selected 
+/// by a context, and weights are trained to minimize coding cost.
+pub struct Mixer {
+    inputs:   Vec<i32>, // Stretched predictions
+    weights:  Vec<i32>, // Weight sets, 16 bit fixed point
+    n:        usize,    // Number of inputs
+    set:      usize,    // Offset of selected weight set
+    pr:       i32,      // Last output probability
+}
+impl Mixer {
+    pub fn new(n: usize, sets: usize) -> Mixer {
+        Mixer {
+            inputs:   Vec::with_capacity(n),
+            weights:  vec![(1 << 16) / n as i32; n * sets],
+            n,
+            set:      0,
+            pr:       2048,
+        }
+    }
+
+    /// Add a stretched prediction.
+    pub fn add(&mut self, st: i32) {
+        debug_assert!(self.inputs.len() < self.n);
+        self.inputs.push(st);
+    }
+
+    /// Select a weight set.
+    pub fn set(&mut self, cxt: usize) {
+        self.set = cxt * self.n;
+    }
+
+    /// Return a 12 bit probability that the next bit is a 1.
+    pub fn p(&mut self) -> i32 {
+        let weights = &self.weights[self.set..self.set + self.n];
+        let dot = self.inputs.iter().zip(weights.iter())
+            .map(|(&x, &w)| (x as i64 * w as i64) >> 16)
+            .sum::<i64>();
+
+        self.pr = squash(dot.clamp(-2047, 2047) as i32);
+        self.pr
+    }
+
+    /// Adjust selected weights to reduce the error of the last prediction,
+    /// then clear the inputs.
+    pub fn update(&mut self, bit: i32) {
+        let err = ((bit << 12) - self.pr) * 6;
+        let weights = &mut self.weights[self.set..self.set + self.n];
+        for (w, &x) in weights.iter_mut().zip(self.inputs.iter()) {
+            *w += (x * err) >> 13;
+        }
+        self.inputs.clear();
+    }
+}
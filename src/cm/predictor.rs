@@ -0,0 +1,166 @@
+use crate::cm::{
+    statemap::StateMap,
+    mixer::Mixer,
+    apm::Apm,
+    matchmodel::MatchModel,
+    logistic::stretch,
+};
+
+const N: usize = 6;           // Number of contexts
+const INPUTS: usize = N + 3;  // Mixer inputs: contexts, match model, bias
+
+/// Finalize a context hash, combining it with the partial byte c0 and 
+/// reducing it to an index of 'bits' bits. Contexts are hashed once per 
+/// nibble to select a bucket of 16 entries, which is then indexed by the 
+/// bits of the current nibble, so that each nibble touches one cache line.
+fn index(h: u32, c0: u32, bits: u32) -> usize {
+    let h = (h ^ c0.wrapping_mul(0x2F0A_5AC5)).wrapping_mul(0x9E37_79B1);
+    (h >> (32 - bits)) as usize
+}
+
+/// Hash a context of order 'order'.
+fn hash(cxt: u32, order: u32) -> u32 {
+    (cxt.wrapping_add(order << 28))
+        .wrapping_mul(0x2000_0101)
+        .rotate_left(16)
+        .wrapping_mul(0x3B9A_CA07)
+}
+
+/// A Predictor estimates the probability that the next bit of 
+/// uncompressed data is a 1. Predictions from order 1, 2, 3, 4 and 6 
+/// contexts, a whole word context, and a match model are combined by a 
+/// Mixer and refined by two APMs.
+pub struct Predictor {
+    sms:     Vec<StateMap>, // One StateMap per context
+    bits:    u32,           // Index size of hashed contexts
+    cxts:    [u32; N],      // Context hashes of current byte
+    bkts:    [usize; N],    // Bucket of each context for current nibble
+    mm:      MatchModel,    // Match model
+    mxr:     Mixer,         // Combines predictions
+    apm1:    Apm,           // Refines prediction with order 0 context
+    apm2:    Apm,           // Refines prediction with order 1 context
+    c0:      u32,           // Partial byte with leading 1 bit
+    c4:      u32,           // Last 4 bytes
+    c8:      u32,           // Preceding 4 bytes
+    word:    u32,           // Hash of current word
+    bit_pos: u32,           // Bits of current byte seen
+    pr:      i32,           // Prediction
+}
+impl Predictor {
+    /// Create a new Predictor using about 3 times 'mem' bytes.
+    pub fn new(mem: usize) -> Predictor {
+        let mem = mem.max(1 << 20);
+        let bits = (mem / 8).trailing_zeros();
+
+        let mut sms = Vec::with_capacity(N);
+        sms.push(StateMap::new(1 << 16, 1023));
+        for _ in 1..N {
+            sms.push(StateMap::new(1 << bits, 1023));
+        }
+
+        let mut pred = Predictor {
+            sms,
+            bits,
+            cxts:    [0; N],
+            bkts:    [0; N],
+            mm:      MatchModel::new(mem),
+            mxr:     Mixer::new(INPUTS, 256),
+            apm1:    Apm::new(256, 7),
+            apm2:    Apm::new(1 << 16, 7),
+            c0:      1,
+            c4:      0,
+            c8:      0,
+            word:    0,
+            bit_pos: 0,
+            pr:      2048,
+        };
+        pred.pr = pred.predict();
+        pred
+    }
+
+    /// Return a 12 bit probability that the next bit is a 1.
+    pub fn p(&self) -> i32 {
+        self.pr
+    }
+
+    /// Update models with the last coded bit and predict the next one.
+    pub fn update(&mut self, bit: i32) {
+        for sm in self.sms.iter_mut() {
+            sm.update(bit);
+        }
+        self.mm.update(bit);
+        self.mxr.update(bit);
+        self.apm1.update(bit);
+        self.apm2.update(bit);
+
+        self.c0 = (self.c0 << 1) | bit as u32;
+        self.bit_pos += 1;
+
+        if self.c0 >= 256 {
+            self.update_byte((self.c0 & 0xFF) as u8);
+            self.c0 = 1;
+            self.bit_pos = 0;
+        }
+        self.pr = self.predict();
+    }
+
+    /// Compute new context hashes at a byte boundary.
+    fn update_byte(&mut self, byte: u8) {
+        self.c8 = (self.c8 << 8) | (self.c4 >> 24);
+        self.c4 = (self.c4 << 8) | byte as u32;
+
+        if byte.is_ascii_alphabetic() {
+            self.word = (self.word ^ byte.to_ascii_lowercase() as u32)
+                .wrapping_mul(0x0100_0193);
+        }
+        else {
+            self.word = 0;
+        }
+
+        self.cxts[0] = (self.c4 & 0xFF) << 8;
+        self.cxts[1] = hash(self.c4 & 0xFFFF, 2);
+        self.cxts[2] = hash(self.c4 & 0xFF_FFFF, 3);
+        self.cxts[3] = hash(self.c4, 4);
+        self.cxts[4] = hash(self.c4 ^ hash(self.c8 & 0xFFFF, 6), 6);
+        self.cxts[5] = hash(self.word ^ hash(self.c4 & 0xFF, 1), 7);
+
+        self.mm.update_byte(byte);
+    }
+
+    fn predict(&mut self) -> i32 {
+        let c0 = self.c0;
+
+        if self.bit_pos == 0 || self.bit_pos == 4 {
+            for i in 1..N {
+                self.bkts[i] = index(self.cxts[i], c0, self.bits - 4) << 4;
+            }
+        }
+        let nibble = if self.bit_pos < 4 { 
+            c0 as usize
+        } 
+        else { 
+            ((c0 as usize) & ((1 << (self.bit_pos - 4)) - 1)) | (1 << (self.bit_pos - 4)) 
+        };
+
+        let p = self.sms[0].p((self.cxts[0] | c0) as usize);
+        self.mxr.add(stretch(p));
+        for i in 1..N {
+            let p = self.sms[i].p(self.bkts[i] + nibble);
+            self.mxr.add(stretch(p));
+        }
+
+        let mut inputs = [0; 2];
+        self.mm.p(c0, self.bit_pos, &mut inputs);
+        self.mxr.add(inputs[0]);
+        self.mxr.add(inputs[1]);
+        self.mxr.add(256);
+
+        self.mxr.set(c0 as usize);
+        let pr = self.mxr.p();
+
+        let p1 = self.apm1.p(pr, c0 as usize);
+        let p2 = self.apm2.p(pr, (c0 | (self.c4 & 0xFF) << 8) as usize);
+        let pr = (pr + p1 + 2 * p2 + 2) >> 2;
+        pr.clamp(1, 4095)
+    }
+}
@@ -0,0 +1,53 @@
+/// A StateMap maps a context to a probability. Each entry stores a 22 bit
+/// probability and a 10 bit count of how many times the context has been
+/// seen. The adaptation rate is initially fast and slows down as the count
+/// increases, up to a limit.
+pub struct StateMap {
+    t:      Vec<u32>, // Context -> probability and count
+    cxt:    usize,    // Context of last prediction
+    limit:  u32,      // Maximum count
+}
+impl StateMap {
+    pub fn new(n: usize, limit: u32) -> StateMap {
+        StateMap {
+            t:      vec![1 << 31; n],
+            cxt:    0,
+            limit,
+        }
+    }
+
+    /// Return a 12 bit probability that the next bit is a 1.
+    pub fn p(&mut self, cxt: usize) -> i32 {
+        self.cxt = cxt;
+        (self.t[cxt] >> 20) as i32
+    }
+
+    /// Move probability of last context towards bit.
+    pub fn update(&mut self, bit: i32) {
+        let entry = &mut self.t[self.cxt];
+        let n = *entry & 1023;
+        let p = (*entry >> 10) as i32;
+
+        if n < self.limit { 
+            *entry += 1; 
+        }
+        else { 
+            *entry = (*entry & 0xFFFFFC00) | self.limit; 
+        }
+        let delta = (((bit << 22) - p) >> 3) as i64 * RECIPROCAL[n as usize] as i64;
+        *entry = entry.wrapping_add(delta as u32 & 0xFFFFFC00);
+    }
+}
+
+/// 16384 / (n + n + 3), used to approximate division by a context's count.
+const RECIPROCAL: [i32; 1024] = reciprocal_table();
+
+const fn reciprocal_table() -> [i32; 1024] {
+    let mut table = [0i32; 1024];
+    let mut i = 0;
+    while i < 1024 {
+        table[i] = 16384 / (i as i32 + i as i32 + 3);
+        i += 1;
+    }
+    table
+}
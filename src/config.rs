@@ -1,6 +1,6 @@
 use std::{
-    fmt,
-    path::PathBuf,
+    fmt, env, fs,
+    path::{Path, PathBuf},
 };
 
 use crate::{
@@ -8,17 +8,25 @@ use crate::{
     error::ConfigError,
     filedata::FileData,
     constant::Version,
+    crypto,
+    volume,
 };
 
 
+/// Environment variable read for a password if none is given.
+const PASSWORD_VAR: &str = "PRISIRV_PASSWORD";
+
 /// Parsing states.
 enum Parse {
     None,
     CreateArchive,
     AppendFiles,
+    UpdateFiles,
     MergeArchives,
     ExtractArchive,
+    TestArchive,
     ExtractFiles,
+    DeleteFiles,
     DirOut,
     Sort,
     Inputs,
@@ -32,16 +40,49 @@ enum Parse {
     Verbose,
     Align,
     Store,
+    Method,
+    Growth,
+    Auto,
+    MinGain,
+    Filter,
+    FilterExt,
+    Words,
+    Index,
+    NoPerms,
+    NoOwner,
+    NoTimes,
+    FollowLinks,
+    Password,
+    PasswordFile,
+    Recipients,
+    Identities,
+    KeyGen,
+    Sign,
+    Detached,
+    Signers,
+    Signing,
+    VerifySignature,
+    VolumeSize,
+    Recovery,
+    Repair,
+    Salvage,
+    Checksum,
 }
 
 #[derive(PartialEq, Copy, Clone, Debug)]
 pub enum Mode {
     CreateArchive,
     ExtractArchive,
+    TestArchive,
     AppendFiles,
+    UpdateFiles,
     ExtractFiles,
+    DeleteFiles,
     MergeArchives,
     ListArchive,
+    GenerateKeys,
+    VerifySignature,
+    Repair,
     None,
 }
 
@@ -51,24 +92,116 @@ pub enum Align {
     Fixed,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum Method {
-    Lzw,
-    Store,
-}
-impl Default for Method {
-    fn default() -> Method {
-        Method::Lzw
-    }
+    #[default]
+    Lzw   = 0,
+    Store = 1,
+    Cm    = 2,
+    Lz77  = 3,
+    Bwt   = 4,
+    LzwEc = 5,
 }
 impl From<u8> for Method {
     fn from(num: u8) -> Method {
         match num {
             0 => Method::Lzw,
+            2 => Method::Cm,
+            3 => Method::Lz77,
+            4 => Method::Bwt,
+            5 => Method::LzwEc,
             _ => Method::Store,
         }
     }
 }
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Method::Lzw   => write!(f, "LZW"),
+            Method::Store => write!(f, "No Compression"),
+            Method::Cm    => write!(f, "Context Mixing"),
+            Method::Lz77  => write!(f, "LZ77"),
+            Method::Bwt   => write!(f, "BWT"),
+            Method::LzwEc => write!(f, "LZW (Entropy Coded)"),
+        }
+    }
+}
+
+/// LZW dictionary growth policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Growth {
+    #[default]
+    Lzw  = 0, // Previous match + first byte of current match
+    Lzmw = 1, // Previous match + current match
+    Lzap = 2, // Previous match + every prefix of current match
+}
+impl From<u8> for Growth {
+    fn from(num: u8) -> Growth {
+        match num {
+            1 => Growth::Lzmw,
+            2 => Growth::Lzap,
+            _ => Growth::Lzw,
+        }
+    }
+}
+impl fmt::Display for Growth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Growth::Lzw  => write!(f, "LZW"),
+            Growth::Lzmw => write!(f, "LZMW"),
+            Growth::Lzap => write!(f, "LZAP"),
+        }
+    }
+}
+
+/// Reversible transform applied to a block before compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    #[default]
+    None,
+    X86,        // x86 CALL/JMP offsets to absolute addresses
+    Delta(u16), // Difference between bytes 'stride' apart, 0 to detect stride
+    Words,      // Frequent words replaced with short codes
+}
+impl Filter {
+    /// Filter id stored in block header.
+    pub fn id(&self) -> u8 {
+        match self {
+            Filter::None     => 0,
+            Filter::X86      => 1,
+            Filter::Delta(_) => 2,
+            Filter::Words    => 3,
+        }
+    }
+    /// Filter parameter stored in block header.
+    pub fn param(&self) -> u16 {
+        match self {
+            Filter::Delta(stride) => *stride,
+            _ => 0,
+        }
+    }
+}
+impl From<(u8, u16)> for Filter {
+    fn from((id, param): (u8, u16)) -> Filter {
+        match id {
+            1 => Filter::X86,
+            2 => Filter::Delta(param),
+            3 => Filter::Words,
+            _ => Filter::None,
+        }
+    }
+}
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::None     => write!(f, "None"),
+            Filter::X86      => write!(f, "x86"),
+            Filter::Delta(0) => write!(f, "Delta (detect stride)"),
+            Filter::Delta(s) => write!(f, "Delta (stride {s})"),
+            Filter::Words    => write!(f, "Word Dictionary"),
+        }
+    }
+}
 
 /// User defined configuration settings.
 #[derive(Clone, Debug)]
@@ -84,7 +217,29 @@ pub struct Config {
     pub blk_sz:     usize,         // Block size
     pub threads:    usize,         // Maximum number of threads
     pub align:      Align,         // Block size exactly as specified or truncated to file boundary
-    pub method:     Method,        // Compression method, 0 = LZW, 1 = No compression
+    pub method:     Method,        // Compression method
+    pub growth:     Growth,        // LZW dictionary growth policy
+    pub auto:       bool,          // Store blocks that don't compress well enough
+    pub min_gain:   u64,           // Minimum size reduction (%) to keep a compressed block
+    pub filter:     Option<Filter>, // Filter for every block, or chosen per block if None
+    pub filter_ext: Vec<(String, Filter)>, // Per-extension filters used when choosing per block
+    pub words:      bool,          // Use word dictionary filter for text when choosing per block
+    pub index:      bool,          // Write an index of block headers at the end of the archive
+    pub perms:      bool,          // Restore permissions on extraction
+    pub owner:      bool,          // Restore ownership on extraction, if running as root
+    pub times:      bool,          // Restore access and modification times on extraction
+    pub follow_links: bool,        // Store targets of links instead of links
+    pub password:   Option<String>, // Password for encrypting or decrypting blocks
+    pub recipients: Vec<[u8; 32]>, // Public keys to encrypt blocks to
+    pub identities: Vec<[u8; 32]>, // Secret keys for decrypting blocks
+    pub sign_key:   Option<[u8; 32]>, // Secret key for signing archives
+    pub detached:   bool,          // Write signature to a separate file
+    pub signers:    Vec<[u8; 32]>, // Public keys trusted to sign archives
+    pub signing:    bool,          // Generate signing keys with 'keygen'
+    pub volume_size: Option<u64>,  // Split archive into volumes of this size
+    pub recovery:   Option<u8>,    // Recovery record redundancy, in percent
+    pub salvage:    bool,          // Extract what's intact from a damaged archive
+    pub checksum:   bool,          // Compare file checksums when updating
     pub arch:       FileData,      // A Prisirv archive
     pub verbose:    bool,          // Print verbose archive contents with 'ls'
 }
@@ -103,10 +258,18 @@ impl Config {
                     parser = Parse::ExtractArchive;
                     continue;
                 }
+                "t" | "test" => {
+                    parser = Parse::TestArchive;
+                    continue;
+                }
                 "a" | "append" => {
                     parser = Parse::AppendFiles;
                     continue;
                 }
+                "u" | "update" => {
+                    parser = Parse::UpdateFiles;
+                    continue;
+                }
                 "p" | "pick" => {
                     parser = Parse::ExtractFiles;
                     continue;
@@ -115,10 +278,26 @@ impl Config {
                     parser = Parse::MergeArchives;
                     continue;
                 }
+                "delete" => {
+                    parser = Parse::DeleteFiles;
+                    continue;
+                }
                 "ls" | "list" => {
                     parser = Parse::List;
                     continue;
                 }
+                "keygen" => {
+                    parser = Parse::KeyGen;
+                    continue;
+                }
+                "verify-signature" => {
+                    parser = Parse::VerifySignature;
+                    continue;
+                }
+                "repair" => {
+                    parser = Parse::Repair;
+                    continue;
+                }
                 "-verbose" => {
                     parser = Parse::Verbose;
                 }
@@ -158,6 +337,91 @@ impl Config {
                 "-store" => {
                     parser = Parse::Store;
                 }
+                "-auto" => {
+                    parser = Parse::Auto;
+                }
+                "-min-gain" => {
+                    parser = Parse::MinGain;
+                    continue;
+                }
+                "-filter" => {
+                    parser = Parse::Filter;
+                    continue;
+                }
+                "-filter-ext" => {
+                    parser = Parse::FilterExt;
+                    continue;
+                }
+                "-words" => {
+                    parser = Parse::Words;
+                }
+                "-index" => {
+                    parser = Parse::Index;
+                }
+                "-no-perms" => {
+                    parser = Parse::NoPerms;
+                }
+                "-no-owner" => {
+                    parser = Parse::NoOwner;
+                }
+                "-no-times" => {
+                    parser = Parse::NoTimes;
+                }
+                "-follow-links" => {
+                    parser = Parse::FollowLinks;
+                }
+                "-password" => {
+                    parser = Parse::Password;
+                    continue;
+                }
+                "-password-file" => {
+                    parser = Parse::PasswordFile;
+                    continue;
+                }
+                "-recipient" | "-recipients" => {
+                    parser = Parse::Recipients;
+                    continue;
+                }
+                "-identity" | "-identities" => {
+                    parser = Parse::Identities;
+                    continue;
+                }
+                "-volume-size" => {
+                    parser = Parse::VolumeSize;
+                    continue;
+                }
+                "-recovery" => {
+                    parser = Parse::Recovery;
+                    continue;
+                }
+                "-sign" => {
+                    parser = Parse::Sign;
+                    continue;
+                }
+                "-detached" => {
+                    parser = Parse::Detached;
+                }
+                "-signer" | "-signers" => {
+                    parser = Parse::Signers;
+                    continue;
+                }
+                "-signing" => {
+                    parser = Parse::Signing;
+                }
+                "-salvage" => {
+                    parser = Parse::Salvage;
+                }
+                "-checksum" => {
+                    parser = Parse::Checksum;
+                }
+                "-method" => {
+                    parser = Parse::Method;
+                    continue;
+                }
+                "-growth" => {
+                    parser = Parse::Growth;
+                    continue;
+                }
                 _ => {},
             }
             match parser {
@@ -166,30 +430,55 @@ impl Config {
                 }
                 Parse::ExtractArchive => {
                     cfg.mode = Mode::ExtractArchive;
-                    cfg.arch = FileData::from(&arg);
+                    cfg.arch = FileData::new(volume::base_path(Path::new(&arg)));
+                }
+                Parse::TestArchive => {
+                    cfg.mode = Mode::TestArchive;
+                    cfg.arch = FileData::new(volume::base_path(Path::new(&arg)));
                 }
                 Parse::AppendFiles => {
                     cfg.mode = Mode::AppendFiles;
-                    cfg.arch = FileData::from(&arg);
+                    cfg.arch = FileData::new(volume::base_path(Path::new(&arg)));
+                }
+                Parse::UpdateFiles => {
+                    cfg.mode = Mode::UpdateFiles;
+                    cfg.arch = FileData::new(volume::base_path(Path::new(&arg)));
                 }
                 Parse::ExtractFiles => {
                     cfg.mode = Mode::ExtractFiles;
-                    cfg.arch = FileData::from(&arg);
+                    cfg.arch = FileData::new(volume::base_path(Path::new(&arg)));
+                }
+                Parse::DeleteFiles => {
+                    cfg.mode = Mode::DeleteFiles;
+                    cfg.arch = FileData::new(volume::base_path(Path::new(&arg)));
                 }
                 Parse::MergeArchives => {
                     cfg.mode = Mode::MergeArchives; 
-                    cfg.arch = FileData::from(&arg);
+                    cfg.arch = FileData::new(volume::base_path(Path::new(&arg)));
                 }
                 Parse::List => {
                     cfg.mode = Mode::ListArchive;
-                    cfg.arch = FileData::from(&arg);
+                    cfg.arch = FileData::new(volume::base_path(Path::new(&arg)));
+                }
+                Parse::KeyGen => {
+                    cfg.mode = Mode::GenerateKeys;
+                    cfg.user_out = arg;
+                }
+                Parse::VerifySignature => {
+                    cfg.mode = Mode::VerifySignature;
+                    cfg.arch = FileData::new(volume::base_path(Path::new(&arg)));
+                }
+                Parse::Repair => {
+                    cfg.mode = Mode::Repair;
+                    cfg.arch = FileData::new(volume::base_path(Path::new(&arg)));
                 }
                 Parse::Verbose => {
                     cfg.verbose = true;
                 }
                 Parse::Inputs => {
                     let path = PathBuf::from(&arg);
-                    if path.exists() {
+                    // Files to delete are archive entries or patterns
+                    if path.exists() || cfg.mode == Mode::DeleteFiles {
                         cfg.inputs.push(FileData::new(path));
                     }
                     else {
@@ -210,22 +499,13 @@ impl Config {
                     }
                 } 
                 Parse::BlkSz => {
-                    let size  = arg.chars().filter(|c|  c.is_numeric()).collect::<String>();
                     let scale = arg.chars().filter(|c| !c.is_numeric()).collect::<String>();
-
-                    let scale = match scale.as_str() {
-                        "B" => 1,
-                        "K" => 1024,
-                        "M" => 1024*1024,
-                        "G" => 1024*1024*1024,
-                        _ => return Err(ConfigError::InvalidBlockMagnitude(scale)),
-                    };
-
-                    if let Ok(size) = size.parse::<usize>() {
-                        cfg.blk_sz = size * scale;
+                    if scale_of(&scale).is_none() {
+                        return Err(ConfigError::InvalidBlockMagnitude(scale));
                     }
-                    else {
-                        return Err(ConfigError::InvalidBlockSize(arg));
+                    match parse_size(&arg) {
+                        Some(size) => cfg.blk_sz = size,
+                        None => return Err(ConfigError::InvalidBlockSize(arg)),
                     }
                 }
                 Parse::Threads => {
@@ -281,9 +561,122 @@ impl Config {
                 Parse::Store => {
                     cfg.method = Method::Store;
                 }
+                Parse::Auto => {
+                    cfg.auto = true;
+                }
+                Parse::Words => {
+                    cfg.words = true;
+                }
+                Parse::Index => {
+                    cfg.index = true;
+                }
+                Parse::NoPerms => {
+                    cfg.perms = false;
+                }
+                Parse::NoOwner => {
+                    cfg.owner = false;
+                }
+                Parse::NoTimes => {
+                    cfg.times = false;
+                }
+                Parse::FollowLinks => {
+                    cfg.follow_links = true;
+                }
+                Parse::Password => {
+                    cfg.password = Some(arg);
+                }
+                Parse::PasswordFile => {
+                    cfg.password = Some(parse_password(&arg)?);
+                }
+                Parse::Recipients => {
+                    cfg.recipients.push(parse_key(&arg)?);
+                }
+                Parse::Identities => {
+                    cfg.identities.push(parse_key(&arg)?);
+                }
+                Parse::VolumeSize => {
+                    match parse_size(&arg) {
+                        Some(size) if size > 0 => cfg.volume_size = Some(size as u64),
+                        _ => return Err(ConfigError::InvalidVolumeSize(arg)),
+                    }
+                }
+                Parse::Recovery => {
+                    match arg.trim_end_matches('%').parse::<u8>() {
+                        Ok(pct) if (1..=100).contains(&pct) => cfg.recovery = Some(pct),
+                        _ => return Err(ConfigError::InvalidRecovery(arg)),
+                    }
+                }
+                Parse::Sign => {
+                    cfg.sign_key = Some(parse_key(&arg)?);
+                }
+                Parse::Detached => {
+                    cfg.detached = true;
+                }
+                Parse::Signers => {
+                    cfg.signers.push(parse_key(&arg)?);
+                }
+                Parse::Signing => {
+                    cfg.signing = true;
+                }
+                Parse::Salvage => {
+                    cfg.salvage = true;
+                }
+                Parse::Checksum => {
+                    cfg.checksum = true;
+                }
+                Parse::MinGain => {
+                    match arg.trim_end_matches('%').parse::<u64>() {
+                        Ok(gain) if gain <= 100 => cfg.min_gain = gain,
+                        _ => return Err(ConfigError::InvalidMinGain(arg)),
+                    }
+                }
+                Parse::Filter => {
+                    if arg == "auto" {
+                        cfg.filter = None;
+                    }
+                    else {
+                        cfg.filter = Some(parse_filter(&arg)?);
+                    }
+                }
+                Parse::FilterExt => {
+                    if let Some((ext, filter)) = arg.split_once(':') {
+                        let ext = ext.trim_start_matches('.').to_string();
+                        cfg.filter_ext.push((ext, parse_filter(filter)?));
+                    }
+                    else {
+                        return Err(ConfigError::InvalidFilterRule(arg));
+                    }
+                }
+                Parse::Method => {
+                    match arg.as_str() {
+                        "lzw"   => cfg.method = Method::Lzw,
+                        "lzw-ec" => cfg.method = Method::LzwEc,
+                        "cm"    => cfg.method = Method::Cm,
+                        "lz77"  => cfg.method = Method::Lz77,
+                        "bwt"   => cfg.method = Method::Bwt,
+                        "store" => cfg.method = Method::Store,
+                        m => {
+                            return Err(ConfigError::InvalidMethod(m.to_string()));
+                        }
+                    }
+                }
+                Parse::Growth => {
+                    match arg.as_str() {
+                        "lzw"  => cfg.growth = Growth::Lzw,
+                        "lzmw" => cfg.growth = Growth::Lzmw,
+                        "lzap" => cfg.growth = Growth::Lzap,
+                        g => {
+                            return Err(ConfigError::InvalidGrowth(g.to_string()));
+                        }
+                    }
+                }
                 Parse::None => {},
             }
         } 
+        // Other processes can see arguments, but not the environment
+        if cfg.password.is_none() {
+            cfg.password = env::var(PASSWORD_VAR).ok().filter(|pw| !pw.is_empty());
+        }
         Ok(cfg)
     }
     pub fn input_total(&self) -> u64 {
@@ -318,6 +711,9 @@ impl fmt::Display for Config {
                         \r Input Size:      {} Bytes
                         \r Output Path:     {}
                         \r Method:          {}
+                        \r Dict Growth:     {}
+                        \r Store Fallback:  {}
+                        \r Filter:          {}
                         \r Sorting by:      {}
                         \r Memory Usage:    {} MiB
                         \r Block Size:      {} {}
@@ -326,9 +722,17 @@ impl fmt::Display for Config {
                         \r=============================================================\n",
                         self.input_total(),
                         self.arch.path.display(),
-                        match self.method {
-                            Method::Lzw   => "LZW",
-                            Method::Store => "No Compression",
+                        self.method,
+                        self.growth,
+                        if self.auto { 
+                            format!("Under {}% gain", self.min_gain) 
+                        } 
+                        else { 
+                            String::from("Off") 
+                        },
+                        match self.filter {
+                            Some(filter) => filter.to_string(),
+                            None => String::from("Auto"),
                         },
                         match self.sort {
                             Sort::None      => "None",
@@ -364,7 +768,20 @@ impl fmt::Display for Config {
                         self.threads
                     )
                 },
-                Mode::AppendFiles => { 
+                Mode::TestArchive => {
+                    write!(f, "
+                        \rPrisirv {version}
+                        \r=============================================================
+                        \r Testing Archive {}:",
+                        self.arch.path.display()
+                    )?;
+                    write!(f, "\n
+                        \r Threads:     {}
+                        \r=============================================================\n",
+                        self.threads
+                    )
+                },
+                Mode::AppendFiles | Mode::UpdateFiles => { 
                     write!(f, "
                         \rPrisirv {version}
                         \r=============================================================
@@ -387,6 +804,9 @@ impl fmt::Display for Config {
                     write!(f, "\n
                         \r Input Size:      {} Bytes
                         \r Method:          {}
+                        \r Dict Growth:     {}
+                        \r Store Fallback:  {}
+                        \r Filter:          {}
                         \r Sorting by:      {}
                         \r Memory Usage:    {} MiB
                         \r Block Size:      {} {}
@@ -394,9 +814,17 @@ impl fmt::Display for Config {
                         \r Threads:         {}
                         \r=============================================================\n",
                         self.input_total(),
-                        match self.method {
-                            Method::Lzw   => "LZW",
-                            Method::Store => "No Compression",
+                        self.method,
+                        self.growth,
+                        if self.auto { 
+                            format!("Under {}% gain", self.min_gain) 
+                        } 
+                        else { 
+                            String::from("Off") 
+                        },
+                        match self.filter {
+                            Some(filter) => filter.to_string(),
+                            None => String::from("Auto"),
                         },
                         match self.sort {
                             Sort::None      => "None",
@@ -462,7 +890,23 @@ impl fmt::Display for Config {
                         self.threads
                     )
                 },
-                Mode::ListArchive => {
+                Mode::DeleteFiles => {
+                    write!(f, "
+                        \rPrisirv {version}
+                        \r=============================================================
+                        \r Deleting files from archive {}:", 
+                        self.arch.path.display()
+                    )?;
+                    for input in self.inputs.iter() {
+                        write!(f, "
+                            \r    {}", 
+                            input.path.display(),
+                        )?;
+                    }
+                    writeln!(f, "\n
+                        \r=============================================================")
+                },
+                Mode::ListArchive | Mode::GenerateKeys | Mode::VerifySignature | Mode::Repair => {
                     Ok(())
                 }
                 Mode::None => {
@@ -490,12 +934,81 @@ impl Default for Config {
             out:       FileData::default(),
             align:     Align::Fixed,
             method:    Method::default(),
+            growth:    Growth::default(),
+            auto:      false,
+            min_gain:  0,
+            filter:    None,
+            filter_ext: Vec::new(),
+            words:     false,
+            index:     false,
+            perms:     true,
+            owner:     true,
+            times:     true,
+            follow_links: false,
+            password:  None,
+            recipients: Vec::new(),
+            identities: Vec::new(),
+            sign_key:  None,
+            detached:  false,
+            signers:   Vec::new(),
+            signing:   false,
+            volume_size: None,
+            recovery:  None,
+            salvage:   false,
+            checksum:  false,
             arch:      FileData::default(),
             verbose:   false,
         }
     }
 }
 
+/// Parse a filter name: 'none', 'x86', 'words', 'delta' or 'deltaN' 
+/// for a delta filter with stride N.
+fn parse_filter(arg: &str) -> Result<Filter, ConfigError> {
+    match arg {
+        "none"  => Ok(Filter::None),
+        "x86"   => Ok(Filter::X86),
+        "words" => Ok(Filter::Words),
+        "delta" => Ok(Filter::Delta(0)),
+        _ => {
+            match arg.strip_prefix("delta").map(|s| s.parse::<u16>()) {
+                Some(Ok(stride)) if stride > 0 => Ok(Filter::Delta(stride)),
+                _ => Err(ConfigError::InvalidFilter(arg.to_string())),
+            }
+        }
+    }
+}
+
+/// Parse a size with a magnitude suffix: B, K, M or G.
+fn parse_size(arg: &str) -> Option<usize> {
+    let size  = arg.chars().filter(|c|  c.is_numeric()).collect::<String>();
+    let scale = arg.chars().filter(|c| !c.is_numeric()).collect::<String>();
+    size.parse::<usize>().ok()?.checked_mul(scale_of(&scale)?)
+}
+
+fn scale_of(scale: &str) -> Option<usize> {
+    match scale {
+        "B" => Some(1),
+        "K" => Some(1024),
+        "M" => Some(1024*1024),
+        "G" => Some(1024*1024*1024),
+        _ => None,
+    }
+}
+
+/// Read a key from a key file.
+pub(crate) fn parse_key(arg: &str) -> Result<[u8; 32], ConfigError> {
+    crypto::read_key(Path::new(arg)).ok_or_else(|| ConfigError::InvalidKey(PathBuf::from(arg)))
+}
+
+/// Read a password from the first line of a password file.
+pub(crate) fn parse_password(arg: &str) -> Result<String, ConfigError> {
+    fs::read_to_string(arg).ok()
+        .and_then(|text| text.lines().next().map(String::from))
+        .filter(|pw| !pw.is_empty())
+        .ok_or_else(|| ConfigError::InvalidPasswordFile(PathBuf::from(arg)))
+}
+
 fn format(size: usize) -> (usize, String) {
     if size >= 1024*1024*1024 {
         (size/1024/1024/1024, String::from("GiB"))
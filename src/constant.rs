@@ -5,6 +5,21 @@ use std::fmt;
 // Magic Number
 pub const MAGIC: u32 = 0x5653_5250;
 
+// Index footer magic number
+pub const INDEX_MAGIC: u32 = 0x4953_5250;
+
+// Index footer size: index position and magic number
+pub const FOOTER_LEN: u64 = 12;
+
+// Signature trailer magic number
+pub const SIG_MAGIC: u32 = 0x5353_5250;
+
+// Recovery record magic number
+pub const RECOVERY_MAGIC: u32 = 0x5253_5250;
+
+// Largest memory usage, set by '-mem 9'
+pub const MAX_MEM: u64 = 1 << 29;
+
 
 #[derive(Default, Debug, Clone, Copy)]
 pub struct Version {
@@ -13,12 +28,31 @@ pub struct Version {
     pub patch: u16,
 }
 impl Version {
+    // Oldest version that can be read
+    pub const OLDEST: Version = Version::new(0, 2, 0);
+
+    // Versions that changed the block header or file table, adding:
+    pub const GROWTH:    Version = Version::new(0, 3, 0); // LZW dictionary growth policy
+    pub const FILTER:    Version = Version::new(0, 4, 0); // Filter id
+    pub const PARAM:     Version = Version::new(0, 5, 0); // Filter parameter
+    pub const CHECKSUM:  Version = Version::new(0, 6, 0); // Per-file checksums
+    pub const METADATA:  Version = Version::new(0, 7, 0); // Permissions, ownership and timestamps
+    pub const ENTRY:     Version = Version::new(0, 8, 0); // Directories and links
+    pub const CRYPT:     Version = Version::new(0, 9, 0); // Encryption parameters
+
+    pub const fn new(major: u16, minor: u16, patch: u16) -> Version {
+        Version { major, minor, patch }
+    }
     pub fn current() -> Version {
-        Version {
-            major: 0,
-            minor: 2,
-            patch: 0,
-        }
+        Version::CRYPT
+    }
+    /// Check if blocks of this version have the fields added in version 'since'.
+    pub fn has(&self, since: Version) -> bool {
+        (self.major, self.minor) >= (since.major, since.minor)
+    }
+    /// Check if the block header and file table layout of this version is known.
+    pub fn readable(&self) -> bool {
+        self.has(Version::OLDEST) && Version::current().has(*self)
     }
 }
 impl fmt::Display for Version {
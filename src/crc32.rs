@@ -45,26 +45,47 @@ pub trait Crc32 {
 }
 impl Crc32 for Path {
     fn crc32(&self) -> u32 {
-        let mut crc32 = 0xFFFFFFFF;
+        let mut crc32 = Crc32Stream::default();
     
         let mut file_in = new_input_file(self).unwrap();
     
         while file_in.fill_buffer() == BufferState::NotEmpty {
-            for byte in file_in.buffer().iter() {
-                crc32 = (crc32 >> 8) ^ CRC_TABLE[((crc32 & 0xff) ^ *byte as u32) as usize]
-            }
+            crc32.update(file_in.buffer());
         }
-        crc32 ^ 0xFFFFFFFF
+        crc32.finish()
     }
 }
 impl Crc32 for &Vec<u8> {
     fn crc32(&self) -> u32 {
-        let mut crc32 = 0xFFFFFFFF;
-    
-        for byte in self.iter() {
-            crc32 = (crc32 >> 8) ^ CRC_TABLE[((crc32 & 0xff) ^ *byte as u32) as usize]
+        let mut crc32 = Crc32Stream::default();
+        crc32.update(self);
+        crc32.finish()
+    }
+}
+
+/// CRC-32 of data that arrives in pieces, such as a file split 
+/// across several blocks.
+#[derive(Clone, Copy)]
+pub struct Crc32Stream {
+    crc32: u32,
+}
+impl Default for Crc32Stream {
+    fn default() -> Crc32Stream {
+        Crc32Stream {
+            crc32: 0xFFFFFFFF,
         }
-        crc32 ^ 0xFFFFFFFF
+    }
+}
+impl Crc32Stream {
+    /// Add data to the checksum.
+    pub fn update(&mut self, data: &[u8]) {
+        for byte in data.iter() {
+            self.crc32 = (self.crc32 >> 8) ^ CRC_TABLE[((self.crc32 & 0xff) ^ *byte as u32) as usize]
+        }
+    }
+    /// Checksum of all data added so far.
+    pub fn finish(&self) -> u32 {
+        self.crc32 ^ 0xFFFFFFFF
     }
 }
 // fn crc32_compute_table() -> [u32; 256] {
@@ -0,0 +1,345 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write, BufReader, BufWriter},
+    fs::{self, OpenOptions},
+    path::Path,
+    fmt,
+};
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+use ed25519_dalek::SigningKey;
+
+use crate::{
+    config::Config,
+    buffered_io::{BufferedRead, BufferedWrite},
+    error::ArchiveError,
+};
+
+// Encrypted blocks store the salt used to derive the key from the
+// password, and the id the block had when it was encrypted, which is
+// used as the nonce. Block ids change when archives are merged, so the
+// original id is kept separately. Each archiving run uses a new salt,
+// and so a new key, so nonces are never reused with the same key.
+//
+// The block's file table and data are encrypted together, with the rest
+// of the header, other than the block id, as associated data.
+//
+// When encrypting to public keys (recipients), the block key is random
+// instead, and the salt only identifies it. Each block header stores an
+// ephemeral X25519 public key and a copy of the block key for each
+// recipient, encrypted with a key derived from the shared secret of the
+// ephemeral key and the recipient's key. Archives can then be created
+// without any secret needed to read them back.
+//
+//  Encryption type (0 none, 1 password, 2 recipients)  u8
+//  Salt                                                16 bytes
+//  Nonce                                               u32
+//  If recipients:
+//      Ephemeral public key                            32 bytes
+//      Number of recipients                            u32
+//      Wrapped key for each recipient                  48 bytes
+
+pub const SALT_LEN:     usize = 16;
+pub const TAG_LEN:      usize = 16;
+pub const KEY_LEN:      usize = 32;
+pub const WRAPPED_LEN:  usize = KEY_LEN + TAG_LEN;
+
+// Recipient counts above this are treated as a damaged header
+const MAX_RECIPIENTS: u32 = 1 << 16;
+
+/// Encryption parameters stored in the header of an encrypted block.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Crypt {
+    pub salt:   [u8; SALT_LEN], // Key derivation salt, or key id if wrapped
+    pub nonce:  u32,            // Block id when encrypted
+    pub wrap:   Option<Wrap>,   // Block key wrapped for each recipient
+}
+impl Crypt {
+    /// Write encryption type and parameters.
+    pub fn write_to<W: Write>(&self, archive: &mut BufWriter<W>) {
+        archive.write_byte(if self.wrap.is_some() { 2 } else { 1 });
+        archive.write_all(&self.salt).unwrap();
+        archive.write_u32(self.nonce);
+        if let Some(wrap) = &self.wrap {
+            archive.write_all(&wrap.ephemeral).unwrap();
+            archive.write_u32(wrap.keys.len() as u32);
+            for key in wrap.keys.iter() {
+                archive.write_all(key).unwrap();
+            }
+        }
+    }
+    /// Read encryption type and parameters of block 'id', if the block 
+    /// is encrypted, failing if the recipient count is implausible.
+    pub fn read_from<R: Read>(archive: &mut BufReader<R>, id: u32) -> Result<Option<Crypt>, ArchiveError> {
+        let kind = archive.read_byte();
+        if kind == 0 {
+            return Ok(None);
+        }
+        let mut crypt = Crypt {
+            salt:   read_array(archive),
+            nonce:  archive.read_u32(),
+            wrap:   None,
+        };
+        if kind == 2 {
+            let ephemeral = read_array(archive);
+            let count = archive.read_u32();
+            if count > MAX_RECIPIENTS {
+                return Err(ArchiveError::InvalidHeader(id));
+            }
+            crypt.wrap = Some(
+                Wrap {
+                    ephemeral,
+                    keys: (0..count).map(|_| read_array(archive)).collect(),
+                }
+            );
+        }
+        Ok(Some(crypt))
+    }
+    /// Size of encryption parameters, not including the type.
+    pub fn size(&self) -> u64 {
+        let wrap = match &self.wrap {
+            Some(wrap) => KEY_LEN + 4 + wrap.keys.len() * WRAPPED_LEN,
+            None => 0,
+        };
+        (SALT_LEN + 4 + wrap) as u64
+    }
+}
+impl fmt::Display for Crypt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.wrap {
+            Some(wrap) => write!(f, "{} recipient(s)", wrap.keys.len()),
+            None => write!(f, "Password"),
+        }
+    }
+}
+
+/// A block key wrapped for each recipient of an archive.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Wrap {
+    pub ephemeral:  [u8; KEY_LEN],          // Ephemeral X25519 public key
+    pub keys:       Vec<[u8; WRAPPED_LEN]>, // Encrypted block keys
+}
+
+fn read_array<R: Read, const N: usize>(archive: &mut BufReader<R>) -> [u8; N] {
+    let mut arr = [0u8; N];
+    for byte in arr.iter_mut() {
+        *byte = archive.read_byte();
+    }
+    arr
+}
+
+/// A key for encrypting and decrypting blocks.
+#[derive(Clone)]
+pub struct BlockKey {
+    key: Key,
+}
+impl BlockKey {
+    /// Derive a key from a password and salt with Argon2id.
+    fn derive(password: &str, salt: &[u8]) -> Result<BlockKey, ArchiveError> {
+        let mut key = Key::default();
+        Argon2::default()
+            .hash_password_into(password.as_bytes(), salt, &mut key)
+            .map_err(|_| ArchiveError::KeyDerivationFailed)?;
+        Ok(BlockKey { key })
+    }
+
+    /// Encrypt block contents, returning ciphertext followed by tag.
+    pub fn encrypt(&self, nonce: u32, msg: &[u8], aad: &[u8]) -> Vec<u8> {
+        ChaCha20Poly1305::new(&self.key)
+            .encrypt(&nonce_from(nonce), Payload { msg, aad })
+            .unwrap()
+    }
+
+    /// Decrypt block contents, failing if the key is wrong or the block
+    /// has been modified.
+    pub fn decrypt(&self, nonce: u32, msg: &[u8], aad: &[u8]) -> Option<Vec<u8>> {
+        ChaCha20Poly1305::new(&self.key)
+            .decrypt(&nonce_from(nonce), Payload { msg, aad })
+            .ok()
+    }
+}
+
+fn nonce_from(id: u32) -> Nonce {
+    let mut nonce = Nonce::default();
+    nonce[..4].copy_from_slice(&id.to_le_bytes());
+    nonce
+}
+
+/// Cipher for wrapping a block key for one recipient, keyed by the
+/// shared secret of the ephemeral and recipient keys. Each of these
+/// keys wraps a single block key, so a zero nonce is used.
+fn wrapping_cipher(secret: &StaticSecret, public: &PublicKey, ephemeral: &PublicKey, recipient: &PublicKey) -> Option<ChaCha20Poly1305> {
+    let shared = secret.diffie_hellman(public);
+    if !shared.was_contributory() {
+        return None;
+    }
+    let mut salt = [0u8; KEY_LEN * 2];
+    salt[..KEY_LEN].copy_from_slice(ephemeral.as_bytes());
+    salt[KEY_LEN..].copy_from_slice(recipient.as_bytes());
+
+    let mut key = Key::default();
+    Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes())
+        .expand(b"prisirv x25519 key wrap", &mut key)
+        .ok()?;
+    Some(ChaCha20Poly1305::new(&key))
+}
+
+fn random<const N: usize>() -> Result<[u8; N], ArchiveError> {
+    let mut arr = [0u8; N];
+    getrandom::getrandom(&mut arr).map_err(|_| ArchiveError::KeyDerivationFailed)?;
+    Ok(arr)
+}
+
+/// Derives, unwraps and caches keys for encrypting and decrypting blocks.
+#[derive(Default)]
+pub struct Keyring {
+    password:    Option<String>,
+    recipients:  Vec<PublicKey>,
+    identities:  Vec<StaticSecret>,
+    keys:        HashMap<[u8; SALT_LEN], BlockKey>,
+    current:     Option<Crypt>, // Parameters of key used for encryption
+}
+impl Keyring {
+    pub fn new(cfg: &Config) -> Keyring {
+        Keyring {
+            password:   cfg.password.clone(),
+            recipients: cfg.recipients.iter().map(|key| PublicKey::from(*key)).collect(),
+            identities: cfg.identities.iter().map(|key| StaticSecret::from(*key)).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Key and parameters for encrypting new blocks, or None if no
+    /// password or recipients were given. Blocks are encrypted to
+    /// recipients if there are any. A new key is created the first
+    /// time this is called.
+    pub fn encryption_key(&mut self) -> Result<Option<(BlockKey, Crypt)>, ArchiveError> {
+        if let Some(crypt) = &self.current {
+            return Ok(Some((self.keys[&crypt.salt].clone(), crypt.clone())));
+        }
+        let salt = random()?;
+
+        let crypt = if !self.recipients.is_empty() {
+            let key = BlockKey { key: Key::from(random::<KEY_LEN>()?) };
+            let secret = StaticSecret::from(random::<KEY_LEN>()?);
+            let ephemeral = PublicKey::from(&secret);
+
+            let mut keys = Vec::with_capacity(self.recipients.len());
+            for recipient in self.recipients.iter() {
+                let cipher = wrapping_cipher(&secret, recipient, &ephemeral, recipient)
+                    .ok_or(ArchiveError::KeyDerivationFailed)?;
+                let wrapped = cipher.encrypt(&Nonce::default(), key.key.as_slice()).unwrap();
+                let mut arr = [0u8; WRAPPED_LEN];
+                arr.copy_from_slice(&wrapped);
+                keys.push(arr);
+            }
+            self.keys.insert(salt, key);
+            Crypt {
+                salt,
+                nonce: 0,
+                wrap: Some(Wrap { ephemeral: ephemeral.to_bytes(), keys }),
+            }
+        }
+        else if self.password.is_some() {
+            Crypt { salt, ..Default::default() }
+        }
+        else {
+            return Ok(None);
+        };
+        let key = self.key(&crypt)?;
+        self.current = Some(crypt.clone());
+        Ok(Some((key, crypt)))
+    }
+
+    /// Key for decrypting blocks with the given encryption parameters.
+    pub fn key(&mut self, crypt: &Crypt) -> Result<BlockKey, ArchiveError> {
+        if let Some(key) = self.keys.get(&crypt.salt) {
+            return Ok(key.clone());
+        }
+        let key = match &crypt.wrap {
+            Some(wrap) => self.unwrap(wrap)?,
+            None => {
+                let password = self.password.as_ref().ok_or(ArchiveError::PasswordRequired)?;
+                BlockKey::derive(password, &crypt.salt)?
+            }
+        };
+        self.keys.insert(crypt.salt, key.clone());
+        Ok(key)
+    }
+
+    /// Unwrap a block key with any identity it was wrapped for.
+    fn unwrap(&self, wrap: &Wrap) -> Result<BlockKey, ArchiveError> {
+        if self.identities.is_empty() {
+            return Err(ArchiveError::IdentityRequired);
+        }
+        let ephemeral = PublicKey::from(wrap.ephemeral);
+
+        for identity in self.identities.iter() {
+            let recipient = PublicKey::from(identity);
+            let cipher = match wrapping_cipher(identity, &ephemeral, &ephemeral, &recipient) {
+                Some(cipher) => cipher,
+                None => continue,
+            };
+            for wrapped in wrap.keys.iter() {
+                if let Ok(key) = cipher.decrypt(&Nonce::default(), wrapped.as_slice()) {
+                    return Ok(BlockKey { key: Key::clone_from_slice(&key) });
+                }
+            }
+        }
+        Err(ArchiveError::NoMatchingIdentity)
+    }
+}
+
+/// Read a hex encoded key from a key file.
+pub fn read_key(path: &Path) -> Option<[u8; KEY_LEN]> {
+    let hex = fs::read_to_string(path).ok()?;
+    let hex = hex.trim().as_bytes();
+    if hex.len() != KEY_LEN * 2 {
+        return None;
+    }
+    let mut key = [0u8; KEY_LEN];
+    for (byte, pair) in key.iter_mut().zip(hex.chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(key)
+}
+
+/// Generate an X25519 key pair, or an Ed25519 key pair if 'signing' is
+/// set, writing the secret key to 'name.key' and the public key to 
+/// 'name.pub'.
+pub fn generate_keys(name: &Path, signing: bool, clobber: bool) -> Result<(), ArchiveError> {
+    let secret = random::<KEY_LEN>()?;
+    let public = match signing {
+        true => SigningKey::from_bytes(&secret).verifying_key().to_bytes(),
+        false => PublicKey::from(&StaticSecret::from(secret)).to_bytes(),
+    };
+
+    let key_path = name.with_extension("key");
+    let pub_path = name.with_extension("pub");
+    for path in [&key_path, &pub_path] {
+        if path.exists() && !clobber {
+            return Err(ArchiveError::FileAlreadyExists(path.to_path_buf()));
+        }
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)] {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    writeln!(options.open(&key_path)?, "{}", hex(&secret))?;
+    fs::write(&pub_path, format!("{}\n", hex(&public)))?;
+    Ok(())
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
@@ -49,6 +49,11 @@ impl fmt::Display for SortError {
 pub enum ConfigError {
     InvalidSortCriteria(String),
     InvalidLvl(String),
+    InvalidMethod(String),
+    InvalidGrowth(String),
+    InvalidMinGain(String),
+    InvalidFilter(String),
+    InvalidFilterRule(String),
     OutOfRangeMemory(u64),
     InvalidMemory(String),
     InvalidBlockSize(String),
@@ -56,6 +61,10 @@ pub enum ConfigError {
     OutOfRangeThreadCount(usize),
     InvalidThreadCount(String),
     InvalidInput(PathBuf),
+    InvalidKey(PathBuf),
+    InvalidPasswordFile(PathBuf),
+    InvalidVolumeSize(String),
+    InvalidRecovery(String),
     InvalidSortMethod(SortError),
     InvalidInsertId(String),
     IoError(io::Error),
@@ -86,6 +95,51 @@ impl fmt::Display for ConfigError {
                     \r'-sort prt n'.\n"
                 )
             }
+            ConfigError::InvalidMethod(m) => {
+                write!(f,  "
+                    \r{m} is not a valid compression method.\n
+                    \rCompression Methods:\n
+                    \r    -method lzw    LZW
+                    \r    -method lzw-ec LZW with entropy coded output
+                    \r    -method cm     Context Mixing
+                    \r    -method lz77   LZ77, window size set by -mem
+                    \r    -method bwt    Burrows-Wheeler transform, one per block
+                    \r    -method store  No compression\n"
+                )
+            }
+            ConfigError::InvalidGrowth(g) => {
+                write!(f,  "
+                    \r{g} is not a valid dictionary growth policy.\n
+                    \rDictionary Growth Policies:\n
+                    \r    -growth lzw   Previous match + next byte
+                    \r    -growth lzmw  Previous match + next match
+                    \r    -growth lzap  Previous match + all prefixes of next match\n"
+                )
+            }
+            ConfigError::InvalidMinGain(gain) => {
+                write!(f, "
+                    \r{gain} is not a valid minimum gain.\n
+                    \rMinimum gain is a percentage from 0 to 100.\n"
+                )
+            }
+            ConfigError::InvalidFilter(filter) => {
+                write!(f, "
+                    \r{filter} is not a valid filter.\n
+                    \rFilters:\n
+                    \r    -filter auto   Choose per block from file types and -filter-ext rules
+                    \r    -filter none   No filter
+                    \r    -filter x86    x86 CALL/JMP address conversion
+                    \r    -filter delta  Delta coding, stride detected per block
+                    \r    -filter deltaN Delta coding with stride N
+                    \r    -filter words  Replace frequent words with short codes\n"
+                )
+            }
+            ConfigError::InvalidFilterRule(rule) => {
+                write!(f, "
+                    \r{rule} is not a valid filter rule.\n
+                    \rFilter rules have the form 'ext:filter', e.g. 'wav:delta2'.\n"
+                )
+            }
             ConfigError::OutOfRangeMemory(mem) => {
                 write!(f, "
                     \r{mem} is outside the valid range of memory options (0..9).\n
@@ -140,6 +194,32 @@ impl fmt::Display for ConfigError {
                     path.display()
                 )
             }
+            ConfigError::InvalidVolumeSize(size) => {
+                write!(f, "
+                    \r{size} is not a valid volume size.\n
+                    \rVolume size is a number followed by B, K, M or G, like '-volume-size 4G'.\n"
+                )
+            }
+            ConfigError::InvalidRecovery(pct) => {
+                write!(f, "
+                    \r{pct} is not a valid recovery record size.\n
+                    \rRecovery record size is a percentage from 1 to 100.\n"
+                )
+            }
+            ConfigError::InvalidKey(path) => {
+                write!(f, "
+                    \r{} is not a valid key file.\n
+                    \rKey files contain a 32 byte key in hex, as written by 'keygen'.\n",
+                    path.display()
+                )
+            }
+            ConfigError::InvalidPasswordFile(path) => {
+                write!(f, "
+                    \r{} is not a valid password file.\n
+                    \rPassword files contain the password on their first line.\n",
+                    path.display()
+                )
+            }
             ConfigError::InvalidSortMethod(err) => {
                 write!(f, "
                     \r{err}\n"
@@ -195,13 +275,28 @@ impl From<SortError> for ConfigError {
 #[derive(Debug)]
 pub enum ArchiveError {
     InvalidMagicNumber(u32),
+    InvalidHeader(u32),
     InvalidVersion(Version),
     IncompatibleVersions,
     FileNotFound(PathBuf),
     IncorrectChecksum(u32),
+    IncorrectFileChecksum(PathBuf),
+    NotSeekable,
+    KeyDerivationFailed,
+    PasswordRequired,
+    IdentityRequired,
+    NoMatchingIdentity,
+    EncryptionKeyRequired(u32),
+    DecryptionFailed(u32),
+    SignatureMissing,
+    InvalidSignature,
+    UntrustedSigner(String),
+    RecoveryRecordMissing,
+    RepairFailed(usize),
     IoError(io::Error),
     FileAlreadyExists(PathBuf),
     FileAccessDenied(PathBuf),
+    UnsafePath(PathBuf),
     CreationTimeError(SystemTimeError),
     InvalidUtf8(Utf8Error),
 }
@@ -228,6 +323,11 @@ impl fmt::Display for ArchiveError {
                     \rDid not find valid magic number in block {id} header.\n"
                 )
             }
+            ArchiveError::InvalidHeader(id) => {
+                write!(f, "
+                    \rBlock {id} header is damaged.\n"
+                )
+            }
             ArchiveError::InvalidVersion(version) => {
                 let current = Version::current();
                 write!(f, "
@@ -251,6 +351,76 @@ impl fmt::Display for ArchiveError {
                     \rBlock {id} checksum is invalid.\n"
                 )
             }
+            ArchiveError::NotSeekable => {
+                write!(f, "
+                    \rThis command needs an archive file, not stdin or stdout.\n"
+                )
+            }
+            ArchiveError::KeyDerivationFailed => {
+                write!(f, "
+                    \rCouldn't derive encryption key from password.\n"
+                )
+            }
+            ArchiveError::PasswordRequired => {
+                write!(f, "
+                    \rArchive is encrypted, a password is required (-password).\n"
+                )
+            }
+            ArchiveError::IdentityRequired => {
+                write!(f, "
+                    \rArchive is encrypted to recipients, an identity is required (-identity).\n"
+                )
+            }
+            ArchiveError::NoMatchingIdentity => {
+                write!(f, "
+                    \rArchive isn't encrypted to any of the given identities.\n"
+                )
+            }
+            ArchiveError::SignatureMissing => {
+                write!(f, "
+                    \rArchive isn't signed.\n"
+                )
+            }
+            ArchiveError::InvalidSignature => {
+                write!(f, "
+                    \rArchive signature is invalid, the archive has been modified
+                    \ror the signature doesn't belong to it.\n"
+                )
+            }
+            ArchiveError::UntrustedSigner(key) => {
+                write!(f, "
+                    \rArchive is signed by {key}, which isn't one of the given signers.\n"
+                )
+            }
+            ArchiveError::RecoveryRecordMissing => {
+                write!(f, "
+                    \rArchive has no intact recovery record.\n"
+                )
+            }
+            ArchiveError::RepairFailed(count) => {
+                write!(f, "
+                    \r{count} damaged shards couldn't be rebuilt, the damage exceeds 
+                    \rthe archive's recovery record.\n"
+                )
+            }
+            ArchiveError::EncryptionKeyRequired(id) => {
+                write!(f, "
+                    \rBlock {id} is encrypted, so '-password' or '-recipient' is needed 
+                    \rto encrypt it again after it's rewritten.\n"
+                )
+            }
+            ArchiveError::DecryptionFailed(id) => {
+                write!(f, "
+                    \rBlock {id} couldn't be decrypted, either the password is wrong
+                    \ror the archive has been modified.\n"
+                )
+            }
+            ArchiveError::IncorrectFileChecksum(path) => {
+                write!(f, "
+                    \r{} checksum is invalid.\n",
+                    path.display()
+                )
+            }
             ArchiveError::IoError(err) => {
                 write!(f, "
                     \r{err}.\n"
@@ -268,6 +438,13 @@ impl fmt::Display for ArchiveError {
                     path.display()
                 )
             }
+            ArchiveError::UnsafePath(path) => {
+                write!(f, "
+                    \r{} would be written outside the output directory, 
+                    \ror links outside it.\n",
+                    path.display()
+                )
+            }
             ArchiveError::CreationTimeError(err) => {
                 write!(f, "
                     \r{err}.\n"
@@ -1,48 +1,94 @@
 use std::{
     io::{
-        BufWriter, BufReader, 
+        self, BufReader, Read,
         Seek, SeekFrom
     },
-    fs::File,
-    path::PathBuf,
+    fs::{self, File, FileTimes},
+    path::{Path, PathBuf, Component},
+    time::SystemTime,
+    collections::{HashMap, HashSet, BTreeSet},
 };
 
 use crate::{
-    filedata::FileData,
+    filedata::{FileData, Entry},
     threads::ThreadPool,
     block::Block,
-    formatting::fmt_file_out_extract,
+    formatting::{fmt_file_out_extract, extract_path},
     config::Config,
     buffered_io::{
-        BufferedWrite, new_input_file, new_output_file, 
+        BufferedWrite, new_input_archive, new_output_file, 
         new_dir,
     },
     error::ArchiveError,
+    archiveinfo::ArchiveInfo,
+    crc32::Crc32Stream,
+    volume::Volumes,
+    constant::{MAGIC, Version},
+    recovery,
+    archivetest::TestReport,
 };
 
-/// Format and return new output file.
-fn next_file(file_in: &FileData, dir_out: &FileData, clobber: bool) -> Result<BufWriter<File>, ArchiveError> {
-    let file_out = fmt_file_out_extract(dir_out, file_in);
-    new_output_file(&file_out, clobber)
+/// Restore a file's permissions, ownership and timestamps, as enabled
+/// in the config.
+fn restore_metadata(path: &Path, file: &FileData, cfg: &Config) -> Result<(), ArchiveError> {
+    if cfg.times {
+        let times = FileTimes::new()
+            .set_accessed(SystemTime::UNIX_EPOCH + file.atime)
+            .set_modified(SystemTime::UNIX_EPOCH + file.mtime);
+        File::open(path)?.set_times(times)?;
+    }
+
+    #[cfg(unix)] {
+        use std::os::unix::fs::{chown, PermissionsExt};
+
+        // Change owner first, as chown can clear setuid and setgid bits
+        let owner = cfg.owner && is_root();
+        if owner {
+            chown(path, Some(file.uid), Some(file.gid))?;
+        }
+        // Archives created on other platforms have no mode bits. Setuid,
+        // setgid and sticky bits are only kept along with the owner.
+        if cfg.perms && file.mode != 0 {
+            let mode = if owner { file.mode } else { file.mode & 0o777 };
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        }
+    }
+    Ok(())
+}
+
+/// Check if running with root privileges, required to change ownership.
+#[cfg(unix)]
+fn is_root() -> bool {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() == 0 }
 }
 
-/// An Extractor extracts archives.
-pub struct Extractor {
-    pub archive:  BufReader<File>,
+/// An Extractor extracts archives, read from an archive file or any 
+/// other reader, such as stdin.
+pub struct Extractor<R: Read> {
+    pub archive:  BufReader<R>,
     pub cfg:      Config,
     tp:           ThreadPool,
+    links:        HashSet<PathBuf>,         // Symlinks created so far
+    dirs:         Vec<(PathBuf, FileData)>, // Directories to restore metadata of
 }
-impl Extractor {
-    /// Create a new Extractor.
-    pub fn new(cfg: Config) -> Result<Extractor, ArchiveError> {
+impl Extractor<Volumes> {
+    /// Create a new Extractor reading an archive file or volume set.
+    pub fn new(cfg: Config) -> Result<Extractor<Volumes>, ArchiveError> {
+        let archive = new_input_archive(&cfg.arch.path)?;
+        Ok(Extractor::from_reader(cfg, archive))
+    }
+}
+impl<R: Read> Extractor<R> {
+    /// Create a new Extractor reading from an existing reader.
+    pub fn from_reader(cfg: Config, archive: BufReader<R>) -> Extractor<R> {
         let tp = ThreadPool::new(0, &cfg);
-        let archive = new_input_file(&cfg.arch.path)?;
         
-        Ok(
-            Extractor { 
-                archive, cfg, tp,
-            }
-        )
+        Extractor { 
+            archive, cfg, tp,
+            links: HashSet::new(),
+            dirs:  Vec::new(),
+        }
     }
 
     /// Decompress blocks and parse blocks into files. A block can span
@@ -51,13 +97,14 @@ impl Extractor {
         new_dir(&self.cfg.out)?;
         
         let mut blk = Block::default();
+        let mut crcs = HashMap::new();
 
         // Read and decompress blocks
         loop {
             blk.read_from(&mut self.archive)?;
             self.tp.decompress_block(blk.clone())?;
 
-            if blk.data.is_empty() {
+            if blk.is_empty() {
                 break;
             }
             
@@ -66,85 +113,402 @@ impl Extractor {
 
         // Write blocks to output 
         loop {
-            if let Some(blk) = self.tp.bq.lock().unwrap().try_get_block() {
+            let blk = self.tp.bq.lock().unwrap().try_get_block()?;
+            if let Some(blk) = blk {
                 // Check for sentinel block
-                if blk.data.is_empty() {
+                if blk.is_empty() {
                     break; 
                 }
 
                 for file in blk.files.iter() {
-                    let mut file_out = next_file(file, &self.cfg.out, self.cfg.clobber)?;
-                    file_out.seek(SeekFrom::Start(file.seg_beg))?;
+                    self.write_segment(file, &blk, &mut crcs)?;
+                }
+            }
+        }
+        self.restore_dirs()
+    }
+
+    /// Decompress every block and check block and file checksums
+    /// without writing any files.
+    pub fn test_archive(&mut self) -> Result<TestReport, ArchiveError> {
+        let mut tables = Vec::new();
+
+        // Read and decompress blocks, keeping file tables of blocks
+        // that fail to decompress
+        loop {
+            let mut blk = Block::default();
+            blk.read_from(&mut self.archive)?;
+            if blk.is_empty() {
+                break;
+            }
+            blk.id = tables.len() as u32;
+            tables.push((blk.ver, blk.files.clone()));
+            self.tp.decompress_block(blk)?;
+        }
 
-                    // Get segment of block containing target file's data.
+        let mut report = TestReport::default();
+        let mut crcs: HashMap<PathBuf, Crc32Stream> = HashMap::new();
+        let mut damaged = HashSet::new();
+
+        // Check segments of each block in order
+        let mut done = 0;
+        while done < tables.len() {
+            let result = self.tp.bq.lock().unwrap().try_get_result();
+            if let Some((id, result)) = result {
+                let (ver, table) = &tables[done];
+                let files = match &result {
+                    Ok(blk) => &blk.files,
+                    Err(_)  => table,
+                };
+                // File checksums were added in version 0.6
+                let chksums = ver.has(Version::CHECKSUM);
+                done += 1;
+                report.blocks.push((id, result.is_ok()));
+
+                for file in files.iter().filter(|file| file.entry == Entry::File) {
                     let beg = file.blk_pos as usize;
-                    let end = (file.blk_pos + (file.seg_end - file.seg_beg)) as usize;
-                
-                    for byte in blk.data[beg..end].iter() {
-                        file_out.write_byte(*byte);
+                    let end = beg + (file.seg_end - file.seg_beg) as usize;
+                    let seg = match &result {
+                        Ok(blk) => blk.data.get(beg..end),
+                        Err(_)  => None,
+                    };
+                    match seg {
+                        Some(seg) => crcs.entry(file.path.clone()).or_default().update(seg),
+                        None => { damaged.insert(file.path.clone()); }
+                    }
+
+                    if file.seg_end == file.len {
+                        let crc = crcs.remove(&file.path).unwrap_or_default();
+                        let ok = !damaged.remove(&file.path) && (!chksums || crc.finish() == file.chksum);
+                        report.files.push((file.path.clone(), ok));
+                    }
+                }
+            }
+        }
+
+        // Files missing their last segment
+        let incomplete = crcs.into_keys().chain(damaged).collect::<BTreeSet<PathBuf>>();
+        report.files.extend(incomplete.into_iter().map(|path| (path, false)));
+        Ok(report)
+    }
+
+    /// Write a file segment from a decompressed block, checking the
+    /// file's checksum and restoring its metadata once its last segment 
+    /// has been written.
+    fn write_segment(&mut self, file: &FileData, blk: &Block, crcs: &mut HashMap<PathBuf, Crc32Stream>) -> Result<(), ArchiveError> {
+        if file.entry != Entry::File {
+            return self.write_entry(file);
+        }
+        let path_out = self.output_path(file)?;
+        let mut file_out = new_output_file(&path_out, self.cfg.clobber)?;
+        file_out.seek(SeekFrom::Start(file.seg_beg))?;
+
+        // Get segment of block containing target file's data.
+        let beg = file.blk_pos as usize;
+        let end = (file.blk_pos + (file.seg_end - file.seg_beg)) as usize;
+        let seg = &blk.data[beg..end];
+    
+        for byte in seg.iter() {
+            file_out.write_byte(*byte);
+        }
+        file_out.flush_buffer();
+
+        let crc = crcs.entry(file.path.clone()).or_default();
+        crc.update(seg);
+
+        if file.seg_end == file.len {
+            let crc = crcs.remove(&file.path).unwrap_or_default();
+            if blk.ver.has(Version::CHECKSUM) && crc.finish() != file.chksum {
+                return Err(ArchiveError::IncorrectFileChecksum(file.path.clone()));
+            }
+            drop(file_out);
+            if blk.ver.has(Version::METADATA) {
+                restore_metadata(&path_out.path, file, &self.cfg)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Recreate a directory or link. Directory metadata is restored once
+    /// everything has been extracted, as writing into a directory changes 
+    /// its timestamps and its permissions could forbid writing into it.
+    fn write_entry(&mut self, file: &FileData) -> Result<(), ArchiveError> {
+        if file.entry == Entry::Dir {
+            let path_out = self.output_path(file)?.path;
+            fs::create_dir_all(&path_out)?;
+            self.dirs.push((path_out, file.clone()));
+            return Ok(());
+        }
+
+        // Links replace whatever is at their path, so only the parent
+        // directories need checking
+        let path_out = extract_path(&self.cfg.out, file);
+        self.check_parents(&path_out, file)?;
+        fmt_file_out_extract(&self.cfg.out, file);
+
+        if path_out.symlink_metadata().is_ok() {
+            if !self.cfg.clobber {
+                return Err(ArchiveError::FileAlreadyExists(path_out));
+            }
+            fs::remove_file(&path_out)?;
+        }
+
+        match &file.entry {
+            Entry::Symlink(target) => {
+                if link_escapes(&self.cfg.out.path, &path_out, target) {
+                    return Err(ArchiveError::UnsafePath(file.path.clone()));
+                }
+                #[cfg(unix)] {
+                    use std::os::unix::fs::{symlink, lchown};
+                    symlink(target, &path_out)?;
+                    if self.cfg.owner && is_root() {
+                        lchown(&path_out, Some(file.uid), Some(file.gid))?;
                     }
-                    file_out.flush_buffer();
                 }
+                #[cfg(windows)] {
+                    std::os::windows::fs::symlink_file(target, &path_out)?;
+                }
+                self.links.insert(path_out);
+            }
+            Entry::Hardlink(target) => {
+                // Target is an earlier entry, so it has already been extracted
+                let target = FileData {
+                    path: target.clone(),
+                    ..Default::default()
+                };
+                if target.path.components().any(|c| c == Component::ParentDir) {
+                    return Err(ArchiveError::UnsafePath(file.path.clone()));
+                }
+                let target_out = extract_path(&self.cfg.out, &target);
+                self.check_parents(&target_out, file)?;
+                if target_out.symlink_metadata().is_err() {
+                    return Err(ArchiveError::FileNotFound(target.path));
+                }
+                fs::hard_link(target_out, &path_out)?;
+                self.links.remove(&path_out);
             }
+            _ => {}
         }
         Ok(())
-    } 
+    }
 
+    /// Output path of a file or directory, creating its parent directories.
+    /// A symlink created earlier at the same path is replaced rather than
+    /// written through.
+    fn output_path(&mut self, file: &FileData) -> Result<FileData, ArchiveError> {
+        let path_out = extract_path(&self.cfg.out, file);
+        self.check_parents(&path_out, file)?;
+        if self.links.remove(&path_out) {
+            fs::remove_file(&path_out)?;
+        }
+        Ok(fmt_file_out_extract(&self.cfg.out, file))
+    }
+
+    /// Fail if any parent directory of 'path' is a symlink created during 
+    /// this extraction, which could lead anywhere, such as a link to '/etc' 
+    /// followed by a file 'link/passwd'.
+    fn check_parents(&self, path: &Path, file: &FileData) -> Result<(), ArchiveError> {
+        if path.ancestors().skip(1).any(|dir| self.links.contains(dir)) {
+            return Err(ArchiveError::UnsafePath(file.path.clone()));
+        }
+        Ok(())
+    }
+
+    /// Restore metadata of extracted directories, deepest first, so 
+    /// restoring a directory can't affect restoring those inside it.
+    fn restore_dirs(&mut self) -> Result<(), ArchiveError> {
+        self.dirs.sort_by(|(a, _), (b, _)| b.cmp(a));
+        for (path, file) in self.dirs.drain(..) {
+            restore_metadata(&path, &file, &self.cfg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check if a symlink at 'path' in output directory 'out' would point 
+/// outside of it, either by an absolute target or too many '..'s.
+fn link_escapes(out: &Path, path: &Path, target: &Path) -> bool {
+    // Number of directories between the output directory and the link
+    let mut depth = path.strip_prefix(out)
+        .map(|rel| rel.components().count() as i64 - 1)
+        .unwrap_or(0);
+
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => {}
+            Component::ParentDir => {
+                depth -= 1;
+                if depth < 0 {
+                    return true;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return true,
+        }
+    }
+    false
+}
+impl<R: Read + Seek> Extractor<R> {
+    /// Decompress only blocks containing selected files, seeking to each
+    /// block using the archive index or block headers. The index isn't 
+    /// covered by the signature, so it isn't used when checking signers.
     pub fn extract_files(&mut self) -> Result<(), ArchiveError> {
         new_dir(&self.cfg.out)?;
         
-        let mut blk = Block::default();
+        let info = match self.cfg.signers.is_empty() {
+            true  => ArchiveInfo::from_reader(&mut self.archive)?,
+            false => ArchiveInfo::from_headers(&mut self.archive)?,
+        };
+        let mut crcs = HashMap::new();
         let mut id = 0;
 
         let paths = self.cfg.inputs.iter()
             .map(|f| f.path.clone())
             .collect::<Vec<PathBuf>>();
 
-        // Read and decompress blocks
-        loop {
-            blk.read_from(&mut self.archive)?;
-
-            if blk.files.iter().any(|f| paths.contains(&f.path)) {
+        // Read and decompress only blocks containing selected files. 
+        // File tables of encrypted blocks can't be read without 
+        // decrypting, so all encrypted blocks are decompressed.
+        for (pos, hdr) in info.blocks() {
+            if hdr.crypt.is_some() || hdr.files.iter().any(|f| paths.contains(&f.path)) {
+                let mut blk = Block::default();
+                self.archive.seek(SeekFrom::Start(pos))?;
+                blk.read_from(&mut self.archive)?;
                 blk.id = id;
                 id += 1;
-                self.tp.decompress_block(blk.clone())?;
+                self.tp.decompress_block(blk)?;
             }
-
-            if blk.data.is_empty() {
-                blk.id = id;
-                self.tp.decompress_block(blk.clone())?;
-                break;
-            }
-
-            blk.next();
         }
 
+        // Sentinel block
+        let mut blk = Block::default();
+        self.archive.seek(SeekFrom::Start(info.end_of_data()))?;
+        blk.read_from(&mut self.archive)?;
+        blk.id = id;
+        self.tp.decompress_block(blk)?;
+
         // Write blocks to output 
         loop {
-            if let Some(mut blk) = self.tp.bq.lock().unwrap().try_get_block() {
+            let blk = self.tp.bq.lock().unwrap().try_get_block()?;
+            if let Some(mut blk) = blk {
                 // Check for sentinel block
-                if blk.data.is_empty() {
+                if blk.is_empty() {
                     break; 
                 }
 
                 blk.files.retain(|file| paths.contains(&file.path));
                 
                 for file in blk.files.iter() {
-                    let mut file_out = next_file(file, &self.cfg.out, self.cfg.clobber)?;
-                    file_out.seek(SeekFrom::Start(file.seg_beg))?;
+                    self.write_segment(file, &blk, &mut crcs)?;
+                }
+            }
+        }
+        self.restore_dirs()
+    }
 
-                    // Get segment of block containing target file's data.
-                    let beg = file.blk_pos as usize;
-                    let end = (file.blk_pos + (file.seg_end - file.seg_beg)) as usize;
-                
-                    for byte in blk.data[beg..end].iter() {
-                        file_out.write_byte(*byte);
+    /// Extract every intact file segment of a damaged archive. Blocks are
+    /// read in order until a header is damaged, then the archive is 
+    /// scanned for the magic number of the next plausible block header.
+    /// Only directories and links are recreated from blocks that fail to 
+    /// decompress. Returns the paths of files that couldn't be extracted 
+    /// completely. Files entirely within lost blocks or encrypted blocks 
+    /// that fail can't be named, as their file table is lost too.
+    pub fn salvage(&mut self) -> Result<Vec<PathBuf>, ArchiveError> {
+        new_dir(&self.cfg.out)?;
+
+        // Blocks end before any recovery record
+        let end = match recovery::record_pos(&mut self.archive)? {
+            Some(pos) => pos,
+            None => self.archive.seek(SeekFrom::End(0))?,
+        };
+        let mut pos = 0;
+
+        // File tables of dispatched blocks, so files of blocks that fail
+        // to decompress can still be reported
+        let mut tables = Vec::new();
+
+        while pos < end {
+            let remaining = end - pos;
+            self.archive.seek(SeekFrom::Start(pos))?;
+
+            // Read past the end as zeros, so damaged lengths can't fail reads
+            let mut reader = BufReader::new(
+                (&mut self.archive).take(remaining).chain(io::repeat(0))
+            );
+            let mut blk = Block::default();
+            if blk.read_salvaged_from(&mut reader, remaining).is_err() {
+                pos = self.next_magic(pos + 1, end)?;
+                continue;
+            }
+            if blk.is_empty() {
+                break;
+            }
+            pos += blk.size();
+            blk.id = tables.len() as u32;
+            tables.push(blk.files.clone());
+            self.tp.decompress_block(blk)?;
+        }
+
+        let mut crcs = HashMap::new();
+        let mut incomplete = BTreeSet::new();
+
+        // Write intact blocks to output
+        let mut done = 0;
+        while done < tables.len() {
+            let result = self.tp.bq.lock().unwrap().try_get_result();
+            if let Some((_, result)) = result {
+                done += 1;
+                let blk = match result {
+                    Ok(blk) => blk,
+                    Err(_) => {
+                        // Entries without data can still be recreated
+                        for file in tables[done - 1].iter() {
+                            if file.entry == Entry::File || self.write_entry(file).is_err() {
+                                incomplete.insert(file.path.clone());
+                            }
+                        }
+                        continue;
+                    }
+                };
+                for file in blk.files.iter() {
+                    match self.write_segment(file, &blk, &mut crcs) {
+                        Ok(()) => {}
+                        Err(ArchiveError::IncorrectFileChecksum(path)) |
+                        Err(ArchiveError::FileNotFound(path)) => {
+                            incomplete.insert(path);
+                        }
+                        Err(err) => return Err(err),
                     }
-                    file_out.flush_buffer();
                 }
             }
         }
-        Ok(())
+
+        // Files missing their last segment
+        incomplete.extend(crcs.into_keys());
+        self.restore_dirs()?;
+        Ok(incomplete.into_iter().collect())
     }
-}
 
+    /// Position of the next magic number at or after 'pos', or 'end'
+    /// if there isn't one.
+    fn next_magic(&mut self, mut pos: u64, end: u64) -> Result<u64, ArchiveError> {
+        let magic = MAGIC.to_le_bytes();
+        let mut buf = vec![0u8; 1 << 16];
+
+        while pos < end {
+            let chunk = &mut buf[..(end - pos).min(1 << 16) as usize];
+            self.archive.seek(SeekFrom::Start(pos))?;
+            self.archive.read_exact(chunk)?;
+
+            if let Some(i) = chunk.windows(4).position(|window| window == magic) {
+                return Ok(pos + i as u64);
+            }
+            // Overlap chunks so a magic number can't be split between them
+            if chunk.len() < 4 {
+                break;
+            }
+            pos += chunk.len() as u64 - 3;
+        }
+        Ok(end)
+    }
+}
@@ -1,15 +1,21 @@
 use std::{
-    fs::DirEntry,
-    path::PathBuf,
+    fs::{File, DirEntry, Metadata},
+    path::{Path, PathBuf},
     ffi::OsStr,
+    io::{self, Read},
+    time::{Duration, SystemTime},
     fmt,
-    io,
 };
 
-/// Files in an archive are represented as file segments. If a file
-/// doesn't cross a block boundary, then 'seg_beg' will be 0 and 'seg_end'
-/// will be equal to len. If a file does cross a block boundary, the segment
-/// boundaries descibe which segment of the file is in that block.
+use crate::{
+    error::ArchiveError,
+    constant::Version,
+};
+
+// Files in an archive are represented as file segments. If a file
+// doesn't cross a block boundary, then 'seg_beg' will be 0 and 'seg_end'
+// will be equal to len. If a file does cross a block boundary, the segment
+// boundaries descibe which segment of the file is in that block.
 //
 //  EXAMPLE:
 //
@@ -40,8 +46,9 @@ use std::{
 //  
 
 /// File type. 
-#[derive(Clone, PartialEq, Eq)]
+#[derive(Clone, PartialEq, Eq, Default)]
 pub enum Type {
+    #[default]
     Unknown,
     Compressed,
     Text,
@@ -57,18 +64,158 @@ impl From<&OsStr> for Type {
                     "zip"  | "7z"   | "xz"   |
                     "gz"   | "zstd" | "bz2" => Type::Compressed,
                     "txt" => Type::Text,
-                    "exe" => Type::Executable,
+                    "exe" | "dll" | "so" => Type::Executable,
                     _     => Type::Unknown,
                 }
             }
         }
     }
 }
-impl Default for Type {
-    fn default() -> Type {
-        Type::Unknown
+impl Type {
+    /// Detect file type from the first bytes of a file, falling back to
+    /// its extension if the file can't be read.
+    pub fn detect(path: &Path) -> Type {
+        let mut head = Vec::with_capacity(SNIFF_LEN as usize);
+        let read = File::open(path).and_then(|file| {
+            file.take(SNIFF_LEN).read_to_end(&mut head)
+        });
+
+        match read {
+            Ok(len) if len > 0 => {}
+            _ => {
+                return match path.extension() {
+                    Some(ext) => Type::from(ext),
+                    None      => Type::Unknown,
+                };
+            }
+        }
+
+        if let Some(kind) = Type::from_magic(&head) {
+            kind
+        }
+        else if is_text(&head) {
+            Type::Text
+        }
+        else {
+            Type::Binary
+        }
+    }
+
+    /// Match magic numbers of common compressed and executable formats.
+    fn from_magic(head: &[u8]) -> Option<Type> {
+        const COMPRESSED: [&[u8]; 17] = [
+            b"\x1F\x8B",                  // gzip
+            b"\x28\xB5\x2F\xFD",          // zstd
+            b"\xFD7zXZ\x00",              // xz
+            b"PK\x03\x04",                // zip
+            b"PK\x05\x06",                // zip (empty)
+            b"7z\xBC\xAF\x27\x1C",        // 7z
+            b"BZh",                       // bzip2
+            b"\x04\x22\x4D\x18",          // lz4
+            b"Rar!\x1A\x07",              // rar
+            b"\x89PNG",                   // PNG
+            b"\xFF\xD8\xFF",              // JPEG
+            b"GIF8",                      // GIF
+            b"%PDF",                      // PDF
+            b"OggS",                      // Ogg
+            b"fLaC",                      // FLAC
+            b"ID3",                       // MP3
+            b"PRSV",                      // Prisirv archive
+        ];
+        const EXECUTABLE: [&[u8]; 5] = [
+            b"\x7FELF",                   // ELF
+            b"\xFE\xED\xFA\xCE",          // Mach-O 32 bit
+            b"\xFE\xED\xFA\xCF",          // Mach-O 64 bit
+            b"\xCE\xFA\xED\xFE",          // Mach-O 32 bit, little endian
+            b"\xCF\xFA\xED\xFE",          // Mach-O 64 bit, little endian
+        ];
+
+        if COMPRESSED.iter().any(|magic| head.starts_with(magic)) {
+            return Some(Type::Compressed);
+        }
+        // Containers identified by a tag after the first 4 bytes
+        if head.len() >= 12 && (&head[4..8] == b"ftyp" || (&head[..4] == b"RIFF" && &head[8..12] == b"WEBP")) {
+            return Some(Type::Compressed);
+        }
+        if EXECUTABLE.iter().any(|magic| head.starts_with(magic)) {
+            return Some(Type::Executable);
+        }
+        // PE: 'MZ' stub with offset to 'PE' signature at 0x3C
+        if head.starts_with(b"MZ") && head.len() >= 0x40 {
+            let pe = u32::from_le_bytes([head[0x3C], head[0x3D], head[0x3E], head[0x3F]]) as usize;
+            if head.get(pe..pe+4) == Some(b"PE\0\0") {
+                return Some(Type::Executable);
+            }
+        }
+        None
+    }
+}
+
+// Bytes read from the start of a file to detect its type
+const SNIFF_LEN: u64 = 4096;
+
+/// Treat data as text if it is valid UTF-8, ignoring a character cut off 
+/// at the end, with no null bytes and few control characters.
+fn is_text(head: &[u8]) -> bool {
+    if let Err(e) = std::str::from_utf8(head) {
+        if e.error_len().is_some() {
+            return false;
+        }
+    }
+    let control = head.iter()
+        .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0C))
+        .count();
+
+    !head.contains(&0) && control * 100 < head.len()
+}
+
+/// Kind of archive entry. Links and directories have no data.
+#[derive(Clone, PartialEq, Eq, Default)]
+pub enum Entry {
+    #[default]
+    File,
+    Dir,
+    Symlink(PathBuf),  // Link target
+    Hardlink(PathBuf), // Path of an earlier entry for the same file
+}
+impl Entry {
+    pub fn id(&self) -> u8 {
+        match self {
+            Entry::File        => 0,
+            Entry::Dir         => 1,
+            Entry::Symlink(_)  => 2,
+            Entry::Hardlink(_) => 3,
+        }
+    }
+    /// Link target, if entry is a link.
+    pub fn target(&self) -> Option<&Path> {
+        match self {
+            Entry::Symlink(target) | Entry::Hardlink(target) => Some(target),
+            _ => None,
+        }
+    }
+}
+impl From<(u8, PathBuf)> for Entry {
+    fn from((id, target): (u8, PathBuf)) -> Entry {
+        match id {
+            1 => Entry::Dir,
+            2 => Entry::Symlink(target),
+            3 => Entry::Hardlink(target),
+            _ => Entry::File,
+        }
     }
 }
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Entry::File             => Ok(()),
+            Entry::Dir              => write!(f, "/"),
+            Entry::Symlink(target)  => write!(f, " -> {}", target.display()),
+            Entry::Hardlink(target) => write!(f, " link to {}", target.display()),
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Default)]
 pub struct FileData {
     pub path:     PathBuf, // File path
@@ -76,32 +223,107 @@ pub struct FileData {
     pub seg_beg:  u64,     // Beginning segment position
     pub seg_end:  u64,     // End segment position
     pub blk_pos:  u64,     // Starting block position
+    pub chksum:   u32,     // CRC-32 of entire file, stored with last segment
+    pub mode:     u32,     // Unix permission bits
+    pub uid:      u32,     // Owner user id
+    pub gid:      u32,     // Owner group id
+    pub atime:    Duration, // Last access time since Unix epoch
+    pub mtime:    Duration, // Last modification time since Unix epoch
     pub kind:     Type,    // File type
+    pub entry:    Entry,   // File, directory or link
     pub new:      bool,    // True if creating a new archive
 }
 impl FileData {
     pub fn new(path: PathBuf) -> FileData {
-        let len = match path.metadata() {
-            Ok(file) => file.len(),
-            Err(_)   => 0,
-        }; 
-        let kind = match path.extension() {
-            Some(ext) => Type::from(ext),
-            None      => Type::Unknown,
-        };
-        FileData { 
+        let mut file = FileData { 
+            kind: Type::detect(&path),
             path, 
-            len,
-            seg_beg: 0,
-            seg_end: len,
-            blk_pos: 0,
-            kind, 
-            new:     false, 
+            ..Default::default()
+        };
+        if let Ok(meta) = file.path.metadata() {
+            file.len     = meta.len();
+            file.seg_end = meta.len();
+            file.set_metadata(&meta);
         }
+        file
     }
-    // Total size of FileData
-    pub fn size(&self) -> u64 {
-        32 + self.path.as_os_str().len() as u64
+    /// Create an entry for a symlink itself rather than its target,
+    /// failing if the target isn't valid UTF-8, as archive paths are.
+    pub fn symlink(path: PathBuf) -> io::Result<FileData> {
+        let meta = path.symlink_metadata()?;
+        let target = path.read_link()?;
+        if target.to_str().is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData, 
+                format!("target of symlink {} is not valid UTF-8", path.display())
+            ));
+        }
+        let mut file = FileData {
+            entry: Entry::Symlink(target),
+            path,
+            ..Default::default()
+        };
+        file.set_metadata(&meta);
+        Ok(file)
+    }
+    /// Create an entry for a directory.
+    pub fn dir(path: PathBuf) -> FileData {
+        let mut file = FileData {
+            entry: Entry::Dir,
+            path,
+            ..Default::default()
+        };
+        if let Ok(meta) = file.path.metadata() {
+            file.set_metadata(&meta);
+        }
+        file
+    }
+    /// Record permissions, ownership and timestamps.
+    fn set_metadata(&mut self, meta: &Metadata) {
+        let since_epoch = |time: io::Result<SystemTime>| {
+            time.ok()
+                .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+                .unwrap_or_default()
+        };
+        self.atime = since_epoch(meta.accessed());
+        self.mtime = since_epoch(meta.modified());
+
+        #[cfg(unix)] {
+            use std::os::unix::fs::MetadataExt;
+            self.mode = meta.mode() & 0o7777;
+            self.uid  = meta.uid();
+            self.gid  = meta.gid();
+        }
+    }
+    /// True if path is '-', meaning stdin or stdout.
+    pub fn is_stdio(&self) -> bool {
+        self.path.as_os_str() == "-"
+    }
+    /// Return an error if path is stdin or stdout, for operations that
+    /// need to seek within an archive.
+    pub fn seekable(&self) -> Result<(), ArchiveError> {
+        if self.is_stdio() {
+            return Err(ArchiveError::NotSeekable);
+        }
+        Ok(())
+    }
+    // Size of a file table entry, excluding the path's null terminator,
+    // in the layout of version 'ver'.
+    pub fn size(&self, ver: Version) -> u64 {
+        let mut size = 8 * 4 + self.path.as_os_str().len() as u64;
+        if ver.has(Version::CHECKSUM) {
+            size += 4;
+        }
+        if ver.has(Version::METADATA) {
+            size += 4 * 3 + 12 * 2;
+        }
+        if ver.has(Version::ENTRY) {
+            size += 1 + match self.entry.target() {
+                Some(target) => target.as_os_str().len() as u64 + 1,
+                None => 0,
+            };
+        }
+        size
     }
 }
 impl From<io::Result<DirEntry>> for FileData {
@@ -0,0 +1,39 @@
+mod x86;
+mod delta;
+mod words;
+
+use crate::config::Filter;
+
+/// Resolve filter parameters that depend on block contents.
+pub fn resolve(filter: Filter, blk: &[u8]) -> Filter {
+    match filter {
+        Filter::Delta(0) => Filter::Delta(delta::detect_stride(blk)),
+        _ => filter,
+    }
+}
+
+/// Apply a reversible filter to a block before compression.
+pub fn encode(filter: Filter, blk: Vec<u8>) -> Vec<u8> {
+    match filter {
+        Filter::None          => blk,
+        Filter::X86           => x86::encode(blk),
+        Filter::Delta(stride) => delta::encode(blk, stride.max(1) as usize),
+        Filter::Words         => words::encode(blk),
+    }
+}
+
+/// Largest size a filter can grow a block of 'len' bytes to. The words
+/// filter can double a block and add a dictionary of its words.
+pub fn max_len(len: usize) -> usize {
+    len.saturating_mul(4).saturating_add(4)
+}
+
+/// Undo the filter applied to a block after decompression.
+pub fn decode(filter: Filter, blk: Vec<u8>) -> Vec<u8> {
+    match filter {
+        Filter::None          => blk,
+        Filter::X86           => x86::decode(blk),
+        Filter::Delta(stride) => delta::decode(blk, stride.max(1) as usize),
+        Filter::Words         => words::decode(blk),
+    }
+}
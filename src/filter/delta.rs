@@ -0,0 +1,54 @@
+// Delta coding replaces each byte with its difference from the byte 
+// 'stride' positions earlier. Fixed width samples and records change 
+// slowly from one to the next, so the differences are mostly small and
+// repetitive even when the raw values aren't.
+
+// Largest stride tried when detecting stride
+const MAX_DETECT: usize = 32;
+
+// Bytes of a block used to detect stride
+const SAMPLE: usize = 1 << 20;
+
+/// Replace each byte with its difference from the byte 'stride' earlier.
+pub fn encode(mut blk: Vec<u8>, stride: usize) -> Vec<u8> {
+    for i in (stride..blk.len()).rev() {
+        blk[i] = blk[i].wrapping_sub(blk[i-stride]);
+    }
+    blk
+}
+
+/// Undo delta coding.
+pub fn decode(mut blk: Vec<u8>, stride: usize) -> Vec<u8> {
+    for i in stride..blk.len() {
+        blk[i] = blk[i].wrapping_add(blk[i-stride]);
+    }
+    blk
+}
+
+/// Choose the stride that minimizes the order 0 entropy of the 
+/// differences in a sample of the block.
+pub fn detect_stride(blk: &[u8]) -> u16 {
+    let sample = &blk[..blk.len().min(SAMPLE)];
+    let mut best = (f64::MAX, 1);
+
+    for stride in 1..=MAX_DETECT.min(sample.len().saturating_sub(1)) {
+        let mut counts = [0u32; 256];
+        for i in stride..sample.len() {
+            counts[sample[i].wrapping_sub(sample[i-stride]) as usize] += 1;
+        }
+        let total = (sample.len() - stride) as f64;
+        let cost = counts.iter()
+            .filter(|&&c| c > 0)
+            .map(|&c| { 
+                let c = c as f64; 
+                -c * (c / total).log2() 
+            })
+            .sum::<f64>() / total;
+
+        // Prefer shorter strides unless a longer one is clearly better
+        if cost < best.0 * 0.98 {
+            best = (cost, stride);
+        }
+    }
+    best.1 as u16
+}
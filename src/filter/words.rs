@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+// Frequent words (runs of ASCII letters) are replaced with one or two
+// byte codes using byte values above 0x7F, which are rare in text:
+//
+//  0x80..=0xBF       One byte code, first 64 words
+//  0xC0..=0xFE, xx   Two byte code, remaining words
+//  0xFF, xx          Literal byte above 0x7F
+//
+// The dictionary is stored at the start of the block as a u32 word
+// count followed by null terminated words, most frequent first.
+
+const ONE_BYTE: usize = 64;
+const MAX_WORDS: usize = ONE_BYTE + 63 * 256;
+const CODE2: u8 = 0xC0;
+const ESCAPE: u8 = 0xFF;
+
+/// Split a block into runs of letters and single non-letter bytes.
+fn tokens(blk: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        if pos >= blk.len() {
+            return None;
+        }
+        let beg = pos;
+        pos += 1;
+        if blk[beg].is_ascii_alphabetic() {
+            while pos < blk.len() && blk[pos].is_ascii_alphabetic() {
+                pos += 1;
+            }
+        }
+        Some(&blk[beg..pos])
+    })
+}
+
+/// Build a dictionary of words worth replacing, most frequent first.
+fn build_dict(blk: &[u8]) -> Vec<&[u8]> {
+    let mut counts: HashMap<&[u8], usize> = HashMap::new();
+    for token in tokens(blk).filter(|t| t.len() > 1) {
+        *counts.entry(token).or_insert(0) += 1;
+    }
+
+    let mut words = counts.into_iter().collect::<Vec<(&[u8], usize)>>();
+    words.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+    let mut dict = Vec::new();
+    for (word, count) in words {
+        if dict.len() == MAX_WORDS {
+            break;
+        }
+        let code_len = if dict.len() < ONE_BYTE { 1 } else { 2 };
+
+        // Keep words that save more than they cost to store
+        if word.len() > code_len && count * (word.len() - code_len) > word.len() + 1 {
+            dict.push(word);
+        }
+    }
+    dict
+}
+
+/// Replace frequent words with codes.
+pub fn encode(blk: Vec<u8>) -> Vec<u8> {
+    if blk.is_empty() {
+        return blk;
+    }
+    let dict = build_dict(&blk);
+    let codes = dict.iter()
+        .enumerate()
+        .map(|(i, word)| (*word, i))
+        .collect::<HashMap<&[u8], usize>>();
+
+    let mut out = Vec::with_capacity(blk.len());
+    out.extend_from_slice(&(dict.len() as u32).to_le_bytes());
+    for word in dict.iter() {
+        out.extend_from_slice(word);
+        out.push(0);
+    }
+
+    for token in tokens(&blk) {
+        match codes.get(token) {
+            Some(&i) if i < ONE_BYTE => {
+                out.push(0x80 + i as u8);
+            }
+            Some(&i) => {
+                let i = i - ONE_BYTE;
+                out.push(CODE2 + (i >> 8) as u8);
+                out.push(i as u8);
+            }
+            None => {
+                for &byte in token.iter() {
+                    if byte > 0x7F {
+                        out.push(ESCAPE);
+                    }
+                    out.push(byte);
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Replace codes with words. Stops early if the block is malformed,
+/// leaving the checksum to report the error.
+pub fn decode(blk: Vec<u8>) -> Vec<u8> {
+    let mut out = Vec::with_capacity(blk.len() * 2);
+    if blk.len() < 4 {
+        return out;
+    }
+    let count = u32::from_le_bytes([blk[0], blk[1], blk[2], blk[3]]) as usize;
+
+    let mut dict = Vec::with_capacity(count.min(MAX_WORDS));
+    let mut pos = 4;
+    while dict.len() < count {
+        match blk[pos..].iter().position(|&b| b == 0) {
+            Some(len) => {
+                dict.push(&blk[pos..pos+len]);
+                pos += len + 1;
+            }
+            None => return out,
+        }
+    }
+
+    let mut bytes = blk[pos..].iter();
+    while let Some(&byte) = bytes.next() {
+        let word = match byte {
+            0x00..=0x7F => {
+                out.push(byte);
+                continue;
+            }
+            ESCAPE => {
+                match bytes.next() {
+                    Some(&byte) => out.push(byte),
+                    None => break,
+                }
+                continue;
+            }
+            0x80..=0xBF => {
+                dict.get((byte - 0x80) as usize)
+            }
+            _ => {
+                match bytes.next() {
+                    Some(&low) => {
+                        dict.get(ONE_BYTE + ((byte - CODE2) as usize) * 256 + low as usize)
+                    }
+                    None => break,
+                }
+            }
+        };
+        match word {
+            Some(word) => out.extend_from_slice(word),
+            None => break,
+        }
+    }
+    out
+}
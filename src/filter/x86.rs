@@ -0,0 +1,45 @@
+// x86 CALL (E8) and JMP (E9) instructions take a 32 bit offset relative
+// to the next instruction, so repeated calls to the same function have 
+// different operands. Converting offsets to absolute addresses makes 
+// them repeat, which helps every method.
+//
+// Only offsets within +-16 MiB (top byte 0x00 or 0xFF) are converted.
+// The address is kept to 25 bits, with bit 24 stored as a top byte of 
+// 0x00 or 0xFF, so converted operands still have a top byte of 0x00 or 
+// 0xFF and the decoder can find them again. Unconverted operands never 
+// do, and are left alone by both sides.
+
+const MASK: u32 = (1 << 25) - 1;
+
+/// Convert relative CALL/JMP offsets to absolute addresses.
+pub fn encode(blk: Vec<u8>) -> Vec<u8> {
+    transform(blk, |offset, pos| offset.wrapping_add(pos))
+}
+
+/// Convert absolute CALL/JMP addresses back to relative offsets.
+pub fn decode(blk: Vec<u8>) -> Vec<u8> {
+    transform(blk, |addr, pos| addr.wrapping_sub(pos))
+}
+
+fn transform(mut blk: Vec<u8>, convert: impl Fn(u32, u32) -> u32) -> Vec<u8> {
+    let mut i = 0;
+    while i + 5 <= blk.len() {
+        if blk[i] & 0xFE != 0xE8 {
+            i += 1;
+            continue;
+        }
+        // Always skip the operand so that both sides visit the same 
+        // opcodes, regardless of which operands were converted.
+        let op = &mut blk[i+1..i+5];
+        if op[3] == 0x00 || op[3] == 0xFF {
+            let val = u32::from_le_bytes([op[0], op[1], op[2], op[3]]);
+            let val = convert(val, (i + 5) as u32) & MASK;
+
+            // Sign extend from 25 bits
+            let val = if val & (1 << 24) != 0 { val | !MASK } else { val };
+            op.copy_from_slice(&val.to_le_bytes());
+        }
+        i += 5;
+    }
+    blk
+}
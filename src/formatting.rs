@@ -46,6 +46,21 @@ pub fn fmt_root(user_out: &str, ex_arch: &Path) -> FileData {
 /// If the parent directory of the output path doesn't exist, it and other 
 /// required directories are created.
 pub fn fmt_file_out_extract(dir_out: &FileData, file_in: &FileData) -> FileData {
+    let path = extract_path(dir_out, file_in);
+
+    if let Some(parent) = path.parent() {
+        if !parent.exists() {
+            create_dir_all(parent).unwrap();
+        }
+    }
+    let mut file_out = FileData::new(path);
+    file_out.seg_beg = file_in.seg_beg;
+    file_out
+}
+
+/// Output path of a file in an extracted archive, as described for 
+/// fmt_file_out_extract, without creating any directories.
+pub fn extract_path(dir_out: &FileData, file_in: &FileData) -> PathBuf {
     let first = file_in.path.components().next();
     let mut file_cmpnts = file_in.path.components();
 
@@ -77,15 +92,6 @@ pub fn fmt_file_out_extract(dir_out: &FileData, file_in: &FileData) -> FileData
         end.push(s);
     }
 
-    let path = dir_out.path.join(end);
-
-    if let Some(parent) = path.parent() {
-        if !parent.exists() {
-            create_dir_all(parent).unwrap();
-        }
-    }
-    let mut file_out = FileData::new(path);
-    file_out.seg_beg = file_in.seg_beg;
-    file_out
+    dir_out.path.join(end)
 }
 
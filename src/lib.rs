@@ -8,28 +8,44 @@ mod threads;
 mod progress;
 mod block;
 mod lzw;
+mod cm;
+mod lz77;
+mod bwt;
+mod filter;
 mod constant;
+mod crypto;
+mod sign;
+mod volume;
+mod recovery;
 pub mod config;
 pub mod crc32;
 pub mod error;
 pub mod archiveinfo;
+pub mod archivetest;
 
 
 use std::{
     fmt,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    io::{self, Read, Write, Seek, BufReader},
+    ops::Range,
+    collections::HashSet,
 };
 
 use crate::{
     archive::Archiver,
     extract::Extractor,
     archiveinfo::ArchiveInfo,
-    filedata::FileData,
-    config::{Config, Mode, Method},
+    archivetest::TestReport,
+    filedata::{FileData, Entry},
+    config::{Config, Mode, Method, Growth, Filter},
     sort::{Sort, sort_files},
     error::{ConfigError, ArchiveError},
     formatting::fmt_root,
     constant::Version,
+    buffered_io::new_input_archive,
+    volume::Volumes,
+    crypto::Keyring,
 };
 
 
@@ -59,18 +75,165 @@ impl Prisirv {
         self
     }
 
-    /// Use LZW compression.
+    /// Choose compression method.
     pub fn method(mut self, method: Method) -> Self {
         self.cfg.method = method;
         self
     }
 
+    /// Choose LZW dictionary growth policy.
+    pub fn growth(mut self, growth: Growth) -> Self {
+        self.cfg.growth = growth;
+        self
+    }
+
     /// Store with no compression.
     pub fn store(mut self) -> Self {
         self.cfg.method = Method::Store;
         self
     }
 
+    /// Apply a filter to every block instead of choosing one per block.
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.cfg.filter = Some(filter);
+        self
+    }
+
+    /// Use a filter for files with extension 'ext' when choosing
+    /// filters per block.
+    pub fn filter_ext(mut self, ext: &str, filter: Filter) -> Self {
+        self.cfg.filter_ext.push((ext.to_string(), filter));
+        self
+    }
+
+    /// Use the word dictionary filter for text when choosing filters 
+    /// per block.
+    pub fn words(mut self) -> Self {
+        self.cfg.words = true;
+        self
+    }
+
+    /// Don't restore permissions on extraction.
+    pub fn no_perms(mut self) -> Self {
+        self.cfg.perms = false;
+        self
+    }
+
+    /// Don't restore ownership on extraction.
+    pub fn no_owner(mut self) -> Self {
+        self.cfg.owner = false;
+        self
+    }
+
+    /// Don't restore access and modification times on extraction.
+    pub fn no_times(mut self) -> Self {
+        self.cfg.times = false;
+        self
+    }
+
+    /// Archive the targets of symlinks and hard links instead of the links.
+    pub fn follow_links(mut self) -> Self {
+        self.cfg.follow_links = true;
+        self
+    }
+
+    /// Encrypt new blocks, or decrypt encrypted blocks, with a key 
+    /// derived from 'password'.
+    pub fn password(mut self, password: &str) -> Self {
+        self.cfg.password = Some(password.to_string());
+        self
+    }
+
+    /// Use the password on the first line of password file 'path'.
+    pub fn password_file(mut self, path: &str) -> Result<Self, ConfigError> {
+        self.cfg.password = Some(config::parse_password(path)?);
+        Ok(self)
+    }
+
+    /// Encrypt new blocks to the public key in key file 'path'. Can be
+    /// used more than once to add recipients.
+    pub fn recipient(mut self, path: &str) -> Result<Self, ConfigError> {
+        self.cfg.recipients.push(config::parse_key(path)?);
+        Ok(self)
+    }
+
+    /// Decrypt blocks encrypted to recipients with the secret key in key
+    /// file 'path'.
+    pub fn identity(mut self, path: &str) -> Result<Self, ConfigError> {
+        self.cfg.identities.push(config::parse_key(path)?);
+        Ok(self)
+    }
+
+    /// Sign the archive with the Ed25519 secret key in key file 'path'.
+    pub fn sign(mut self, path: &str) -> Result<Self, ConfigError> {
+        self.cfg.sign_key = Some(config::parse_key(path)?);
+        Ok(self)
+    }
+
+    /// Write the signature to 'archive.prsv.sig' instead of the archive.
+    pub fn detached(mut self) -> Self {
+        self.cfg.detached = true;
+        self
+    }
+
+    /// Trust signatures made with the public key in key file 'path'. 
+    /// Extraction is refused unless the archive is signed by a trusted
+    /// key, if any are given.
+    pub fn signer(mut self, path: &str) -> Result<Self, ConfigError> {
+        self.cfg.signers.push(config::parse_key(path)?);
+        Ok(self)
+    }
+
+    /// Generate Ed25519 signing keys with generate_keys().
+    pub fn signing(mut self) -> Self {
+        self.cfg.signing = true;
+        self
+    }
+
+    /// Split the archive into volumes of at most 'size' bytes, named 
+    /// 'name.prsv.001', 'name.prsv.002' and so on.
+    pub fn volume_size(mut self, size: u64) -> Self {
+        self.cfg.volume_size = Some(size);
+        self
+    }
+
+    /// Add a recovery record with 'pct' percent redundancy, so damaged
+    /// parts of the archive can be rebuilt with repair().
+    pub fn recovery(mut self, pct: u8) -> Result<Self, ConfigError> {
+        if (1..=100).contains(&pct) {
+            self.cfg.recovery = Some(pct);
+        }
+        else {
+            return Err(ConfigError::InvalidRecovery(pct.to_string()));
+        }
+        Ok(self)
+    }
+
+    /// Write an index of block headers at the end of the archive.
+    pub fn index(mut self) -> Self {
+        self.cfg.index = true;
+        self
+    }
+
+    /// Compare file checksums as well as lengths and modification times
+    /// when updating.
+    pub fn checksum(mut self) -> Self {
+        self.cfg.checksum = true;
+        self
+    }
+
+    /// Store blocks that compress by less than 'min_gain' percent.
+    pub fn auto(mut self, min_gain: u64) -> Result<Self, ConfigError> {
+        if min_gain <= 100 {
+            self.cfg.auto = true;
+            self.cfg.min_gain = min_gain;
+        }
+        else {
+            return Err(ConfigError::InvalidMinGain(min_gain.to_string()));
+        }
+        Ok(self)
+    }
+
     /// Sort files before solid archiving.
     pub fn sort(mut self, method: Sort) -> Self {
         self.cfg.sort = method;
@@ -125,11 +288,22 @@ impl Prisirv {
         Ok(self)
     }
 
-    /// Choose existing archive.
+    /// Choose archive entries to delete, by path or by pattern where '*'
+    /// matches any run of characters and '?' any one character. Unlike
+    /// inputs, entries don't need to exist.
+    pub fn entries(mut self, entries: &[&str]) -> Self {
+        for entry in entries.iter() {
+            self.cfg.inputs.push(FileData::new(PathBuf::from(entry)));
+        }
+        self
+    }
+
+    /// Choose existing archive, or '-' to extract an archive from stdin.
+    /// Volume sets can be chosen by the archive name or first volume.
     pub fn arch(mut self, input: &str) -> Result<Self, ConfigError> {
         let path = PathBuf::from(input);
-        if path.exists() {
-            self.cfg.arch = FileData::new(path);
+        if volume::exists(&path) || input == "-" {
+            self.cfg.arch = FileData::new(volume::base_path(&path));
         }
         else {
             return Err(ConfigError::InvalidInput(path));
@@ -137,32 +311,65 @@ impl Prisirv {
         Ok(self)
     }
 
-    /// Create an archive from inputs.
+    /// Create an archive from inputs, written to stdout if the output
+    /// path is '-'.
     pub fn create_archive(mut self) -> Result<(), ArchiveError> {
+        if self.cfg.user_out == "-" {
+            self.cfg.quiet = true;
+            self.create_archive_to(io::stdout())?;
+            return Ok(());
+        }
         self.cfg.mode = Mode::CreateArchive;
         self.cfg.arch = fmt_root(&self.cfg.user_out, &self.cfg.inputs[0].path);
         self.cfg.arch.path.set_extension("prsv");
         self.cfg.arch.new = true;
-        sort_inputs(&mut self.cfg.inputs, self.cfg.sort);
+        sort_inputs(&mut self.cfg.inputs, self.cfg.sort, self.cfg.follow_links)?;
         println!("{}", self.cfg);
         Archiver::new(self.cfg).create_archive()?;
         Ok(())
     }
 
+    /// Create an archive from inputs, written to any writer, such as a 
+    /// Vec<u8> or Cursor, and return the writer.
+    pub fn create_archive_to<W: Write>(mut self, writer: W) -> Result<W, ArchiveError> {
+        if self.cfg.recovery.is_some() {
+            return Err(ArchiveError::NotSeekable);
+        }
+        self.cfg.mode = Mode::CreateArchive;
+        self.cfg.arch = FileData::from("-");
+        self.cfg.arch.new = true;
+        sort_inputs(&mut self.cfg.inputs, self.cfg.sort, self.cfg.follow_links)?;
+        Archiver::new(self.cfg).create_archive_to(writer)
+    }
+
     /// Append inputs to archive.
     pub fn append_files(mut self) -> Result<(), ArchiveError> {
         self.cfg.mode = Mode::AppendFiles;
+        self.cfg.arch.seekable()?;
         self.cfg.clobber = true;
         self.cfg.arch.seg_beg = !0; // Don't truncate archive
-        sort_inputs(&mut self.cfg.inputs, self.cfg.sort);
+        sort_inputs(&mut self.cfg.inputs, self.cfg.sort, self.cfg.follow_links)?;
         println!("{}", self.cfg);
         Archiver::new(self.cfg).append_files()?;
         Ok(())
     }
 
+    /// Append only inputs that are new or have changed since they were 
+    /// last added to archive, returning the paths appended.
+    pub fn update_files(mut self) -> Result<Vec<PathBuf>, ArchiveError> {
+        self.cfg.mode = Mode::UpdateFiles;
+        self.cfg.arch.seekable()?;
+        self.cfg.clobber = true;
+        self.cfg.arch.seg_beg = !0; // Don't truncate archive
+        sort_inputs(&mut self.cfg.inputs, self.cfg.sort, self.cfg.follow_links)?;
+        println!("{}", self.cfg);
+        Archiver::new(self.cfg).update_files()
+    }
+
     /// Merge archives together.
     pub fn merge_archives(mut self) -> Result<(), ArchiveError> {
         self.cfg.mode = Mode::MergeArchives;
+        self.cfg.arch.seekable()?;
         self.cfg.clobber = true;
         self.cfg.arch.seg_beg = !0; // Don't truncate archive
         println!("{}", self.cfg);
@@ -170,29 +377,174 @@ impl Prisirv {
         Ok(())
     }
 
-    /// Extract an archive.
+    /// Delete files matching the chosen entries from archive, returning
+    /// the paths deleted. Entries also match files under a directory.
+    pub fn delete_files(mut self) -> Result<Vec<PathBuf>, ArchiveError> {
+        self.cfg.mode = Mode::DeleteFiles;
+        self.cfg.arch.seekable()?;
+        println!("{}", self.cfg);
+        Archiver::new(self.cfg).delete_files()
+    }
+
+    /// Extract an archive, read from stdin if the archive is '-'.
     pub fn extract_archive(mut self) -> Result<(), ArchiveError> {
+        if self.cfg.arch.is_stdio() {
+            return self.extract_archive_from(io::stdin());
+        }
+        let archive = self.open_verified()?;
         self.cfg.mode = Mode::ExtractArchive;
         self.cfg.out = fmt_root(&self.cfg.user_out, &self.cfg.arch.path);
         println!("{}", self.cfg);
-        Extractor::new(self.cfg)?.extract_archive()?;
+        Extractor::from_reader(self.cfg, archive).extract_archive()?;
         Ok(())
     }
 
+    /// Extract every intact file segment of a damaged archive, skipping
+    /// damaged blocks, and return the paths of files that couldn't be 
+    /// extracted completely. Signatures aren't checked, as a damaged 
+    /// archive's signature is invalid.
+    pub fn salvage_archive(mut self) -> Result<Vec<PathBuf>, ArchiveError> {
+        self.cfg.mode = Mode::ExtractArchive;
+        self.cfg.arch.seekable()?;
+        self.cfg.out = fmt_root(&self.cfg.user_out, &self.cfg.arch.path);
+        println!("{}", self.cfg);
+        Extractor::new(self.cfg)?.salvage()
+    }
+
+    /// Decompress every block and check block and file checksums, 
+    /// without writing any files.
+    pub fn test_archive(mut self) -> Result<TestReport, ArchiveError> {
+        if self.cfg.arch.is_stdio() {
+            return self.test_archive_from(io::stdin());
+        }
+        let archive = self.open_verified()?;
+        self.cfg.mode = Mode::TestArchive;
+        println!("{}", self.cfg);
+        Extractor::from_reader(self.cfg, archive).test_archive()
+    }
+
+    /// Test an archive read from any reader, such as stdin or a Cursor.
+    /// Signatures can't be checked before testing a stream.
+    pub fn test_archive_from<R: Read>(mut self, reader: R) -> Result<TestReport, ArchiveError> {
+        if !self.cfg.signers.is_empty() {
+            return Err(ArchiveError::NotSeekable);
+        }
+        self.cfg.mode = Mode::TestArchive;
+        println!("{}", self.cfg);
+        Extractor::from_reader(self.cfg, BufReader::with_capacity(4096, reader)).test_archive()
+    }
+
+    /// Extract an archive read from any reader, such as stdin or a Cursor,
+    /// to the output path, or the current directory if none is given. 
+    /// Signatures can't be checked before extracting a stream.
+    pub fn extract_archive_from<R: Read>(mut self, reader: R) -> Result<(), ArchiveError> {
+        if !self.cfg.signers.is_empty() {
+            return Err(ArchiveError::NotSeekable);
+        }
+        self.cfg.mode = Mode::ExtractArchive;
+        self.cfg.out = self.stream_out();
+        println!("{}", self.cfg);
+        Extractor::from_reader(self.cfg, BufReader::with_capacity(4096, reader)).extract_archive()
+    }
+
     /// Extract inputs from archive.
     pub fn extract_files(mut self) -> Result<(), ArchiveError> {
         self.cfg.mode = Mode::ExtractFiles;
+        self.cfg.arch.seekable()?;
+        let archive = self.open_verified()?;
         self.cfg.out = fmt_root(&self.cfg.user_out, &self.cfg.arch.path);
         println!("{}", self.cfg);
-        Extractor::new(self.cfg)?.extract_files()?;
+        Extractor::from_reader(self.cfg, archive).extract_files()?;
         Ok(())
     }
 
+    /// Extract inputs from an archive read from any seekable reader, 
+    /// to the output path, or the current directory if none is given.
+    pub fn extract_files_from<R: Read + Seek>(mut self, reader: R) -> Result<(), ArchiveError> {
+        self.cfg.mode = Mode::ExtractFiles;
+        self.cfg.out = self.stream_out();
+        println!("{}", self.cfg);
+        let mut archive = BufReader::with_capacity(4096, reader);
+        if !self.cfg.signers.is_empty() {
+            sign::verify(&mut archive, None, &self.cfg.signers)?;
+            archive.rewind()?;
+        }
+        Extractor::from_reader(self.cfg, archive).extract_files()
+    }
+
     /// Get information about archive.
     pub fn info(mut self) -> Result<ArchiveInfo, ArchiveError> {
         self.cfg.mode = Mode::ListArchive;
+        self.cfg.arch.seekable()?;
         println!("{}", self.cfg);
-        ArchiveInfo::new(&self.cfg.arch)
+        self.read_info(&mut new_input_archive(&self.cfg.arch.path)?)
+    }
+
+    /// Get information about an archive read from any seekable reader.
+    /// File tables of encrypted blocks are listed if a password or 
+    /// identity is given.
+    pub fn info_from<R: Read + Seek>(mut self, reader: R) -> Result<ArchiveInfo, ArchiveError> {
+        self.cfg.mode = Mode::ListArchive;
+        println!("{}", self.cfg);
+        self.read_info(&mut BufReader::with_capacity(4096, reader))
+    }
+
+    /// Read block headers, decrypting file tables if possible.
+    fn read_info<R: Read + Seek>(&self, archive: &mut BufReader<R>) -> Result<ArchiveInfo, ArchiveError> {
+        let mut info = ArchiveInfo::from_reader(archive)?;
+        if self.cfg.password.is_some() || !self.cfg.identities.is_empty() {
+            info.decrypt_files(archive, &mut Keyring::new(&self.cfg))?;
+        }
+        Ok(info)
+    }
+
+    /// Generate a key pair, writing the identity to 'name.key' and the 
+    /// recipient to 'name.pub', where 'name' is the output path.
+    pub fn generate_keys(mut self) -> Result<(), ArchiveError> {
+        self.cfg.mode = Mode::GenerateKeys;
+        crypto::generate_keys(Path::new(&self.cfg.user_out), self.cfg.signing, self.cfg.clobber)
+    }
+
+    /// Verify the archive's signature, detached or embedded, returning 
+    /// the signer's public key. If any signers are given, the archive 
+    /// must be signed by one of them.
+    pub fn verify_signature(mut self) -> Result<[u8; 32], ArchiveError> {
+        self.cfg.mode = Mode::VerifySignature;
+        self.cfg.arch.seekable()?;
+        let detached = sign::detached_path(&self.cfg.arch.path);
+        sign::verify(&mut new_input_archive(&self.cfg.arch.path)?, Some(&detached), &self.cfg.signers)
+    }
+
+    /// Rebuild damaged parts of an archive from its recovery record,
+    /// returning the ranges of the archive that were rebuilt.
+    pub fn repair(mut self) -> Result<Vec<Range<u64>>, ArchiveError> {
+        self.cfg.mode = Mode::Repair;
+        self.cfg.arch.seekable()?;
+        self.cfg.arch.seg_beg = !0; // Don't truncate archive
+        recovery::repair(&self.cfg.arch)
+    }
+
+    /// Open the archive, refusing it if not signed by any given signer.
+    /// The archive is read through the same handle it was verified with,
+    /// so it can't be swapped after verification.
+    fn open_verified(&self) -> Result<BufReader<Volumes>, ArchiveError> {
+        let mut archive = new_input_archive(&self.cfg.arch.path)?;
+        if !self.cfg.signers.is_empty() {
+            let detached = sign::detached_path(&self.cfg.arch.path);
+            sign::verify(&mut archive, Some(&detached), &self.cfg.signers)?;
+            archive.rewind()?;
+        }
+        Ok(archive)
+    }
+
+    /// Output directory when extracting an archive that isn't a file.
+    fn stream_out(&self) -> FileData {
+        if self.cfg.user_out.is_empty() {
+            FileData::from(".")
+        }
+        else {
+            FileData::from(self.cfg.user_out.as_str())
+        }
     }
 }
 
@@ -240,10 +592,16 @@ impl fmt::Display for Prisirv {
         REQUIRED:
            c,  create           Create archive
            x,  extract          Extract archive
+           t,  test             Test archive integrity without extracting
            a,  append           Append files to archive
+           u,  update           Append new and changed files to archive
            p,  pick             Extract select files from archive
+           delete               Delete files from archive
            m,  merge            Merge archives together
            ls                   List info about archive
+           keygen               Generate identity (.key) and recipient (.pub) keys
+           verify-signature     Verify archive signature
+           repair               Rebuild damaged archive from its recovery record
                 
         One of the above commands must be used, and all are mutually exclusive.
                 
@@ -254,16 +612,59 @@ impl fmt::Display for Prisirv {
           -blk,  -block-size    Specify block size       (Default - 10 MiB)
           -threads              Specify thread count     (Default - 4)
           -sort                 Sort files               (Default - none)
+          -method               Compression method       (Default - lzw)
+          -growth               LZW dictionary growth    (Default - lzw)
+          -min-gain             Minimum gain with -auto  (Default - 0%)
+          -filter               Block filter             (Default - auto)
+          -filter-ext           Filter rules, ext:filter
+          -password             Encrypt or decrypt blocks with password (insecure)
+          -password-file        Read password from first line of file
+          -recipient            Encrypt blocks to public key files (.pub)
+          -identity             Decrypt blocks with secret key files (.key)
+          -sign                 Sign archive with secret key file (.key)
+          -signer               Trusted signer public key files (.pub)
+          -volume-size          Split archive into volumes of this size
+          -recovery             Add recovery record of this percent redundancy
                 
-        Options '-memory', '-block-size', and '-sort' have no effect on extraction.
+        Options '-memory', '-block-size', '-sort', '-method', '-growth', '-min-gain', 
+        '-filter' and '-filter-ext' have no effect on extraction. Encrypted archives 
+        need '-password' or '-identity' to be extracted or listed. Blocks are encrypted
+        to recipients instead of a password if both are given.
+        A password given with '-password' is visible to other users in the process list,
+        so prefer '-password-file' or the PRISIRV_PASSWORD environment variable, which
+        is used when neither option is given.
+        Extraction is refused unless the archive is signed by one of the '-signer' keys,
+        if any are given. A detached signature is used instead of an embedded one if present.
+        Files to delete can be given as archived paths or patterns, where '*' matches 
+        any characters and '?' any one character.
+        Split archives are named name.prsv.001, name.prsv.002 and so on, and can be used
+        by either name.prsv or the first volume with any command.
+        A recovery record is kept when appending to or merging into an archive that has one.
                 
         FLAGS:
           -q,  -quiet           Suppresses output other than errors
           -clobber              Allow file clobbering
           -file-align           Truncate blocks to align with file boundaries
           -store                Store files with no compression
+          -auto                 Store blocks that compress poorly
+          -words                Use word dictionary filter for text
+          -index                Write an index for fast listing and picking
+          -no-perms             Don't restore permissions
+          -no-owner             Don't restore ownership
+          -no-times             Don't restore access and modification times
+          -follow-links         Store link targets instead of links
+          -detached             Write signature to archive.prsv.sig
+          -signing              Generate signing keys with keygen
+          -salvage              Extract what's intact from a damaged archive
+          -checksum             Compare file checksums with update
                 
-        Flags '-file-align' and 'store' have no effect on extraction.
+        Flags '-file-align', '-store', '-auto', '-words', '-index' and '-follow-links' 
+        have no effect on extraction. An archive's index is used automatically if present.
+        Flags '-no-perms', '-no-owner' and '-no-times' only affect extraction. 
+        Ownership is only restored when running as root.
+        With '-salvage', damaged blocks are skipped and incomplete files are listed.
+        Update compares length and modification time with a file's newest entry, 
+        and its checksum too with '-checksum'.
                 
         Sorting Methods:
           -sort ext      Sort by extension
@@ -274,6 +675,27 @@ impl fmt::Display for Prisirv {
           -sort accd     Sort by last access time
           -sort mod      Sort by last modification time
                 
+        Compression Methods:
+          -method lzw    LZW
+          -method lzw-ec LZW with entropy coded output
+          -method cm     Context Mixing
+          -method lz77   LZ77, window size set by -mem
+          -method bwt    Burrows-Wheeler transform, one per block
+          -method store  No compression
+                
+        Dictionary Growth (lzw and lzw-ec):
+          -growth lzw    Previous match + next byte
+          -growth lzmw   Previous match + next match
+          -growth lzap   Previous match + all prefixes of next match
+                
+        Filters:
+          -filter auto   Choose per block from file types and -filter-ext rules
+          -filter none   No filter
+          -filter x86    x86 CALL/JMP address conversion
+          -filter delta  Delta coding, stride detected per block
+          -filter deltaN Delta coding with stride N
+          -filter words  Replace frequent words with short codes
+                
         Memory Options:
           -mem 0  6 MB   -mem 5  99 MB
           -mem 1  9 MB   -mem 6  195 MB
@@ -297,45 +719,150 @@ impl fmt::Display for Prisirv {
                
             prisirv append /foo/qux.prsv -inputs foo.txt
                
+        Append files in directory [/baz] that changed since the last update to archive [/foo/qux.prsv]:
+               
+            prisirv update /foo/qux.prsv -inputs /baz
+               
         Extract file [foo.txt] from archive [/foo/qux.prsv]:
                
             prisirv pick /foo/qux.prsv -inputs foo.txt
                
+        Delete directory [/baz/secrets] and all [.key] files from archive [/foo/qux.prsv]:
+               
+            prisirv delete /foo/qux.prsv -inputs /baz/secrets \"*.key\"
+               
         List information about archive [/foo/qux.prsv]:
                
             prisirv ls /foo/qux.prsv
+               
+        Check archive [/foo/qux.prsv] is intact without extracting it:
+               
+            prisirv test /foo/qux.prsv
+               
+        Stream archive of directory [/baz] over ssh, extracting it on another host:
+               
+            prisirv create -inputs /baz -output-path - | ssh host prisirv extract -
+               
+        Generate keys [backup.key] and [backup.pub], then create an archive that can 
+        only be read with [backup.key]:
+               
+            prisirv keygen backup
+            prisirv create -inputs /baz -recipient backup.pub
+            prisirv extract baz.prsv -identity backup.key
+               
+        Sign an archive, then check it was signed by [release.key] before extracting:
+               
+            prisirv keygen release -signing
+            prisirv create -inputs /baz -sign release.key
+            prisirv verify-signature baz.prsv -signer release.pub
+            prisirv extract baz.prsv -signer release.pub
+               
+        Create an archive with 10% redundancy, then rebuild it after it's damaged:
+               
+            prisirv create -inputs /baz -recovery 10
+            prisirv repair baz.prsv
         
         "
         )
     }
 }
 
-fn sort_inputs(inputs: &mut Vec<FileData>, sort: Sort) {
-    while expand(inputs).is_some() {}
+fn sort_inputs(inputs: &mut Vec<FileData>, sort: Sort, follow_links: bool) -> Result<(), ArchiveError> {
+    if !follow_links {
+        for input in inputs.iter_mut() {
+            if input.path.is_symlink() {
+                *input = FileData::symlink(input.path.clone())?;
+            }
+        }
+    }
+    let mut visited = HashSet::new();
+    while expand(inputs, follow_links, &mut visited)? {}
     inputs.sort_by(|f1, f2|
         sort_files(f1, f2, sort).unwrap()
     );
+    if !follow_links {
+        link_hardlinks(inputs);
+    }
+    Ok(())
 }
 
-/// Removes all directories from inputs and pushes the contents of the
-/// directories to inputs. An additional iteration is needed for each 
-/// level of nested directories.
-fn expand(inputs: &mut Vec<FileData>) -> Option<usize> {
+/// Replaces all directories in inputs with directory entries and pushes
+/// the contents of the directories to inputs. An additional iteration is 
+/// needed for each level of nested directories, so returns true while 
+/// any directories were expanded. Symlinks are kept as links unless 
+/// following links, in which case directories already expanded, such as
+/// a symlink to a parent directory, are skipped.
+fn expand(inputs: &mut Vec<FileData>, follow_links: bool, visited: &mut HashSet<(u64, u64)>) -> Result<bool, ArchiveError> {
     let mut dirs: Vec<(usize, PathBuf)> = Vec::new();
 
     for (i, input) in inputs.iter().enumerate() {
-        if input.path.is_dir() {
+        if input.entry == Entry::File && input.path.is_dir() {
             dirs.push((i, input.path.clone()));
         }
     }
-    for (i, dir) in dirs.iter() {
-        for file in dir.read_dir().unwrap().map(FileData::from) {
+    // Remove in reverse order so earlier indices stay valid
+    for (i, dir) in dirs.iter().rev() {
+        inputs.swap_remove(*i);
+        if let Some(id) = dir_id(dir)? {
+            if !visited.insert(id) {
+                continue;
+            }
+        }
+        inputs.push(FileData::dir(dir.clone()));
+
+        for entry in dir.read_dir()? {
+            let path = entry?.path();
+            let file = if !follow_links && path.is_symlink() {
+                FileData::symlink(path)?
+            }
+            else {
+                FileData::new(path)
+            };
             inputs.push(file);
         }
-        inputs.swap_remove(*i);
     }
-    if !dirs.is_empty() {
-        return Some(0);
+    Ok(!dirs.is_empty())
+}
+
+/// Device and inode number of a directory, identifying it however it 
+/// was reached.
+#[cfg(unix)]
+fn dir_id(dir: &Path) -> io::Result<Option<(u64, u64)>> {
+    use std::os::unix::fs::MetadataExt;
+    let meta = dir.metadata()?;
+    Ok(Some((meta.dev(), meta.ino())))
+}
+
+#[cfg(not(unix))]
+fn dir_id(_dir: &Path) -> io::Result<Option<(u64, u64)>> {
+    Ok(None)
+}
+
+/// Replace files that are hard links to an earlier file with links to 
+/// that file, so the data is only stored once.
+#[cfg(unix)]
+fn link_hardlinks(inputs: &mut [FileData]) {
+    use std::{collections::HashMap, os::unix::fs::MetadataExt};
+
+    let mut seen: HashMap<(u64, u64), PathBuf> = HashMap::new();
+
+    for input in inputs.iter_mut().filter(|input| input.entry == Entry::File) {
+        let meta = match input.path.metadata() {
+            Ok(meta) if meta.is_file() && meta.nlink() > 1 => meta,
+            _ => continue,
+        };
+        match seen.get(&(meta.dev(), meta.ino())) {
+            Some(target) => {
+                input.entry   = Entry::Hardlink(target.clone());
+                input.len     = 0;
+                input.seg_end = 0;
+            }
+            None => {
+                seen.insert((meta.dev(), meta.ino()), input.path.clone());
+            }
+        }
     }
-    None
-}
\ No newline at end of file
+}
+
+#[cfg(not(unix))]
+fn link_hardlinks(_inputs: &mut [FileData]) {}
\ No newline at end of file
@@ -0,0 +1,4 @@
+pub mod encoder;
+pub mod decoder;
+mod token;
+mod constant;
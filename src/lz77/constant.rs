@@ -0,0 +1,3 @@
+pub const MIN_MATCH: usize = 4;   // Shortest match worth coding
+pub const NICE_LEN:  usize = 256; // Stop searching once a match is this long
+pub const MAX_CHAIN: usize = 128; // Maximum hash chain candidates checked
@@ -0,0 +1,44 @@
+use crate::{
+    cm::coder::Decoder,
+    lz77::token::{Token, TokenModel},
+};
+
+/// Decompress a block compressed with LZ77. Corrupted data can claim 
+/// any length, so blocks claiming more than 'limit' bytes aren't decoded.
+pub fn decompress(blk_in: Vec<u8>, limit: usize) -> Vec<u8> {
+    if blk_in.len() < 8 {
+        return Vec::new();
+    }
+    let mut len = [0u8; 8];
+    len.copy_from_slice(&blk_in[..8]);
+    let len = u64::from_le_bytes(len) as usize;
+    if len > limit {
+        return Vec::new();
+    }
+
+    let mut model = TokenModel::new();
+    let mut dec = Decoder::new(blk_in[8..].to_vec());
+    let mut blk: Vec<u8> = Vec::with_capacity(len);
+
+    while blk.len() < len && !dec.exhausted() {
+        match model.decode(&mut dec) {
+            Token::Literal(byte) => {
+                blk.push(byte);
+            }
+            Token::Match(match_len, dist) => {
+                // Corrupted data, leave it to the checksum to report.
+                if dist == 0 || dist > blk.len() {
+                    break;
+                }
+                // Copy one byte at a time, since a match may overlap
+                // the bytes it produces, stopping at the block's length.
+                let start = blk.len() - dist;
+                for i in start..start + match_len.min(len - blk.len()) {
+                    blk.push(blk[i]);
+                }
+            }
+        }
+        model.prev = *blk.last().unwrap();
+    }
+    blk
+}
@@ -0,0 +1,153 @@
+use crate::{
+    cm::coder::Encoder,
+    lz77::{
+        token::{Token, TokenModel},
+        constant::{MIN_MATCH, NICE_LEN, MAX_CHAIN},
+    },
+};
+
+/// Finds matches in a sliding window using hash chains. 'head' maps a 
+/// hash of the next MIN_MATCH bytes to the most recent position with that 
+/// hash, and 'prev' links each position in the window to the previous 
+/// position with the same hash. Positions are stored offset by 1 so that
+/// 0 can mark an empty slot.
+struct MatchFinder {
+    head:    Vec<u32>,
+    prev:    Vec<u32>,
+    window:  usize,
+    bits:    u32,
+}
+impl MatchFinder {
+    fn new(window: usize) -> MatchFinder {
+        let bits = (window / 4).trailing_zeros().clamp(16, 24);
+        MatchFinder {
+            head:    vec![0; 1 << bits],
+            prev:    vec![0; window],
+            window,
+            bits,
+        }
+    }
+    fn hash(&self, data: &[u8], pos: usize) -> usize {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&data[pos..pos + MIN_MATCH]);
+        (u32::from_le_bytes(bytes).wrapping_mul(0x9E37_79B1) >> (32 - self.bits)) as usize
+    }
+
+    /// Add position to hash chains.
+    fn insert(&mut self, data: &[u8], pos: usize) {
+        if pos + MIN_MATCH <= data.len() {
+            let hash = self.hash(data, pos);
+            self.prev[pos & (self.window - 1)] = self.head[hash];
+            self.head[hash] = pos as u32 + 1;
+        }
+    }
+
+    /// Return the length and distance of the longest match at pos, trying
+    /// distance 'rep' first. A later candidate must be strictly longer
+    /// to be chosen, so shorter distances are preferred.
+    fn find(&self, data: &[u8], pos: usize, rep: usize) -> (usize, usize) {
+        let max = data.len() - pos;
+        if max < MIN_MATCH {
+            return (0, 0);
+        }
+        let mut best = (0, 0);
+
+        if rep > 0 && rep <= pos {
+            let len = match_len(data, pos - rep, pos, max);
+            if len >= MIN_MATCH {
+                best = (len, rep);
+            }
+        }
+
+        let mut cand = self.head[self.hash(data, pos)] as usize;
+        let mut chain = 0;
+        while cand > 0 && chain < MAX_CHAIN && best.0 < NICE_LEN.min(max) {
+            let cpos = cand - 1;
+            let dist = pos - cpos;
+            if dist >= self.window {
+                break;
+            }
+            // Check the byte that would extend the best match first.
+            if data[cpos + best.0] == data[pos + best.0] {
+                let len = match_len(data, cpos, pos, max);
+                if len > best.0 {
+                    best = (len, dist);
+                }
+            }
+            let next = self.prev[cpos & (self.window - 1)] as usize;
+            // Slots older than the window may be overwritten by newer 
+            // positions, so stop unless the chain keeps moving backwards.
+            if next >= cand {
+                break;
+            }
+            cand = next;
+            chain += 1;
+        }
+        if best.0 >= MIN_MATCH { best } else { (0, 0) }
+    }
+}
+
+/// Length of the common prefix of data[a..] and data[b..], up to max bytes.
+fn match_len(data: &[u8], a: usize, b: usize, max: usize) -> usize {
+    let mut len = 0;
+    while len < max && data[a + len] == data[b + len] {
+        len += 1;
+    }
+    len
+}
+
+/// Compress a block using LZ77 with a window of 'mem' bytes. Matches are 
+/// found with hash chains, and a match is deferred by one byte (lazy 
+/// matching) if a longer match starts at the next byte. Tokens are coded
+/// with adaptive models and an arithmetic coder. The uncompressed length 
+/// is stored first so the decoder knows when to stop.
+pub fn compress(blk_in: Vec<u8>, mem: usize) -> Vec<u8> {
+    if blk_in.is_empty() {
+        return Vec::new();
+    }
+    let mut mf = MatchFinder::new(mem.max(1 << 16).next_power_of_two());
+    let mut model = TokenModel::new();
+    let mut enc = Encoder::new();
+    enc.out.extend_from_slice(&(blk_in.len() as u64).to_le_bytes());
+
+    let data = &blk_in;
+    let mut rep = 0;
+    let mut pending = None;
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let (len, dist) = match pending.take() {
+            Some(m) => m,
+            None => mf.find(data, pos, rep),
+        };
+        mf.insert(data, pos);
+
+        if len >= MIN_MATCH && pos + 1 < data.len() {
+            let next = mf.find(data, pos + 1, rep);
+            if next.0 > len {
+                model.encode(&mut enc, &Token::Literal(data[pos]));
+                model.prev = data[pos];
+                pending = Some(next);
+                pos += 1;
+                continue;
+            }
+        }
+
+        if len >= MIN_MATCH {
+            model.encode(&mut enc, &Token::Match(len, dist));
+            model.prev = data[pos + len - 1];
+            for i in pos + 1..pos + len {
+                mf.insert(data, i);
+            }
+            rep = dist;
+            pos += len;
+        }
+        else {
+            model.encode(&mut enc, &Token::Literal(data[pos]));
+            model.prev = data[pos];
+            pos += 1;
+        }
+    }
+    enc.flush();
+    enc.out
+}
@@ -0,0 +1,120 @@
+use crate::{
+    cm::coder::{Encoder, Decoder, Model},
+    lz77::constant::MIN_MATCH,
+};
+
+/// A literal byte or a match of 'len' bytes starting 'dist' bytes back.
+pub enum Token {
+    Literal(u8),
+    Match(usize, usize),
+}
+
+/// Models an integer as a 5 bit length (slot) followed by the bits below 
+/// the leading 1. The top 4 of those bits are modeled per slot, and the 
+/// rest are coded directly.
+struct IntModel {
+    slot:  Vec<Model>,
+    low:   Vec<Model>,
+}
+impl IntModel {
+    fn new() -> IntModel {
+        IntModel {
+            slot: vec![Model::default(); 32],
+            low:  vec![Model::default(); 32 * 16],
+        }
+    }
+    fn encode(&mut self, enc: &mut Encoder, value: usize) {
+        let x = value as u32 + 1;
+        let slot = 31 - x.leading_zeros();
+        enc.encode_tree(&mut self.slot, slot, 5);
+
+        let top = slot.min(4);
+        let rest = slot - top;
+        let low = &mut self.low[slot as usize * 16..];
+        enc.encode_tree(low, (x >> rest) & ((1 << top) - 1), top);
+        enc.encode_direct(x & ((1 << rest) - 1), rest);
+    }
+    fn decode(&mut self, dec: &mut Decoder) -> usize {
+        let slot = dec.decode_tree(&mut self.slot, 5);
+
+        let top = slot.min(4);
+        let rest = slot - top;
+        let low = &mut self.low[slot as usize * 16..];
+        let mut x = (1 << top) | dec.decode_tree(low, top);
+        x = (x << rest) | dec.decode_direct(rest);
+        (x - 1) as usize
+    }
+}
+
+/// Adaptive models for coding a stream of tokens. Whether the next token
+/// is a literal or match is modeled using the kinds of the last two tokens. 
+/// Literals are modeled using the previous byte, and a match can reuse the 
+/// distance of the previous match.
+pub struct TokenModel {
+    kind:   [Model; 4], // Literal or match
+    rep:    [Model; 4], // Match reuses last distance
+    len:    IntModel,   // Match length
+    dist:   IntModel,   // Match distance
+    lit:    Vec<Model>, // Previous byte -> literal
+    state:  usize,      // Kinds of last two tokens
+    last:   usize,      // Distance of last match
+    pub prev: u8,       // Last byte of last token
+}
+impl TokenModel {
+    pub fn new() -> TokenModel {
+        TokenModel {
+            kind:   [Model::default(); 4],
+            rep:    [Model::default(); 4],
+            len:    IntModel::new(),
+            dist:   IntModel::new(),
+            lit:    vec![Model::default(); 256 * 256],
+            state:  0,
+            last:   0,
+            prev:   0,
+        }
+    }
+
+    /// Code a token. The caller updates 'prev' afterwards.
+    pub fn encode(&mut self, enc: &mut Encoder, token: &Token) {
+        match *token {
+            Token::Literal(byte) => {
+                enc.encode_model(0, &mut self.kind[self.state]);
+                let lit = &mut self.lit[self.prev as usize * 256..];
+                enc.encode_tree(lit, byte as u32, 8);
+                self.state = (self.state << 1) & 3;
+            }
+            Token::Match(len, dist) => {
+                enc.encode_model(1, &mut self.kind[self.state]);
+                let rep = (dist == self.last) as i32;
+                enc.encode_model(rep, &mut self.rep[self.state]);
+                if rep == 0 {
+                    self.dist.encode(enc, dist - 1);
+                }
+                self.len.encode(enc, len - MIN_MATCH);
+                self.state = ((self.state << 1) | 1) & 3;
+                self.last = dist;
+            }
+        }
+    }
+
+    /// Decode a token. The caller updates 'prev' afterwards.
+    pub fn decode(&mut self, dec: &mut Decoder) -> Token {
+        if dec.decode_model(&mut self.kind[self.state]) == 0 {
+            let lit = &mut self.lit[self.prev as usize * 256..];
+            self.state = (self.state << 1) & 3;
+            Token::Literal(dec.decode_tree(lit, 8) as u8)
+        }
+        else {
+            let dist = if dec.decode_model(&mut self.rep[self.state]) == 1 {
+                self.last
+            }
+            else {
+                self.dist.decode(dec) + 1
+            };
+            let len = self.len.decode(dec) + MIN_MATCH;
+            self.state = ((self.state << 1) | 1) & 3;
+            self.last = dist;
+            Token::Match(len, dist)
+        }
+    }
+}
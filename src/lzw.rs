@@ -1,4 +1,6 @@
 pub mod encoder;
 pub mod decoder;
 pub mod code;
-pub mod constant;
\ No newline at end of file
+pub mod entropy;
+pub mod constant;
+mod growth;
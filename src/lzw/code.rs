@@ -6,9 +6,25 @@ use crate::lzw::constant::{
     RESET,
 };
 
+/// A source of LZW codes. A reader tracks the current code length, 
+/// adjusting it after returning a LEN_UP or RESET code.
+pub trait CodeRead {
+    fn get_code(&mut self) -> Option<u32>;
+}
+
+/// A sink for LZW codes. A writer tracks the current code length, 
+/// adjusting it after writing a LEN_UP or RESET code, and flushes its 
+/// output after writing DATA_END.
+pub trait CodeWrite {
+    fn write(&mut self, code: u32);
+    fn code_len(&self) -> u32;
+    fn finish(self) -> Vec<u8>;
+}
+
+/// Reads codes packed into 32 bit little endian words.
 pub struct CodeReader {
     stream:        Box<dyn Iterator<Item = u8>>,
-    pub code_len:  u32,
+    code_len:      u32,
     code:          u32,
     count:         u32,
 }
@@ -21,7 +37,7 @@ impl CodeReader {
             count:     0,
         }
     }
-    pub fn get_code(&mut self) -> Option<u32> {
+    fn unpack(&mut self) -> Option<u32> {
         loop {
             match self.stream.next() {
                 Some(byte) => {
@@ -65,11 +81,28 @@ impl CodeReader {
     }
 }
 
+impl CodeRead for CodeReader {
+    fn get_code(&mut self) -> Option<u32> {
+        let code = self.unpack()?;
+        match code {
+            LEN_UP => {
+                self.code_len += 1;
+            }
+            RESET => {
+                self.code_len = 9;
+            }
+            _ => {},
+        }
+        Some(code)
+    }
+}
+
+/// Packs codes into 32 bit little endian words.
 pub struct CodeWriter {
     pck:           u32,
     pck_len:       u32,
-    pub out:       Vec<u8>,
-    pub code_len:  u32,
+    out:           Vec<u8>,
+    code_len:      u32,
 }
 impl CodeWriter {
     pub fn new() -> CodeWriter {
@@ -80,12 +113,25 @@ impl CodeWriter {
             code_len:  9,
         }
     }
+    fn write_code(&mut self, code: u32) {
+        self.out.push((code & 0xFF) as u8);
+        self.out.push(((code >> 8) & 0xFF) as u8);
+        self.out.push(((code >> 16) & 0xFF) as u8);
+        self.out.push((code >> 24) as u8);
+    }
+}
+impl Default for CodeWriter {
+    fn default() -> CodeWriter {
+        CodeWriter::new()
+    }
+}
+impl CodeWrite for CodeWriter {
     /// Split code in two, assuming it crosses a packed code boundary.
     /// If the entire code fits in the current packed code, codeu will
     /// simply be 0. Otherwise, add the first part of the code (codel)
     /// to the current packed code, output the packed code, reset it,
     /// and add the remaining part of the code (codeu).
-    pub fn write(&mut self, code: u32) {
+    fn write(&mut self, code: u32) {
         let rem_len = 32 - self.pck_len;
 
         let codel = code & (0xFFFFFFFF >> self.pck_len);
@@ -125,10 +171,10 @@ impl CodeWriter {
             _ => {},
         }
     }
-    fn write_code(&mut self, code: u32) {
-        self.out.push((code & 0xFF) as u8);
-        self.out.push(((code >> 8) & 0xFF) as u8);
-        self.out.push(((code >> 16) & 0xFF) as u8);
-        self.out.push((code >> 24) as u8);
+    fn code_len(&self) -> u32 {
+        self.code_len
+    }
+    fn finish(self) -> Vec<u8> {
+        self.out
     }
 }
\ No newline at end of file
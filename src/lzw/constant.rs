@@ -1,3 +1,5 @@
 pub const DATA_END: u32 = 257;
 pub const LEN_UP: u32 = 258;
-pub const RESET: u32 = 259;
\ No newline at end of file
+pub const RESET: u32 = 259;
+// Longest string stored in the dictionary
+pub const MAX_STR: usize = 30;
@@ -1,9 +1,14 @@
-use crate::lzw::{
-    code::CodeReader,
-    constant::{
-        DATA_END,
-        LEN_UP,
-        RESET,
+use crate::{
+    config::Growth,
+    lzw::{
+        code::{CodeRead, CodeReader},
+        entropy::EntropyCodeReader,
+        growth::new_strings,
+        constant::{
+            DATA_END,
+            LEN_UP,
+            RESET,
+        },
     },
 };
 
@@ -49,9 +54,7 @@ impl Dictionary {
     fn reset(&mut self) {
         // Skip code 0
         self.code = 1;
-        for i in self.strings.iter_mut() {
-            *i = 0;
-        }
+        self.strings.clear();
         for i in self.codes.iter_mut() {
             *i = 0;
         }
@@ -66,45 +69,41 @@ impl Dictionary {
 struct Decoder {
     dict:    Dictionary,
     string:  Vec<u8>,
+    growth:  Growth,
     pub blk: Vec<u8>,
 }
 impl Decoder {
-    fn new(mem: usize) -> Decoder {
+    fn new(mem: usize, growth: Growth) -> Decoder {
         Decoder {
             dict:   Dictionary::new(mem/4),
             string: Vec::new(),
             blk:    Vec::new(),
+            growth,
         }
     }
-    fn decompress(&mut self, blk_in: Vec<u8>) {
-        let mut stream = CodeReader::new(blk_in);
-
-        loop { 
-            if let Some(code) = stream.get_code() {
-                match code {
-                    DATA_END => {
-                        break;
-                    }
-                    LEN_UP => {
-                        stream.code_len += 1;
-                    }
-                    RESET => {
-                        stream.code_len = 9;
-                        self.dict.reset();
-                        self.string.clear();
-                    }
-                    _ => {
-                        self.output_string(code);
+    fn decompress<R: CodeRead>(&mut self, mut stream: R) {
+        while let Some(code) = stream.get_code() {
+            match code {
+                DATA_END => {
+                    break;
+                }
+                LEN_UP => {}
+                RESET => {
+                    self.dict.reset();
+                    self.string.clear();
+                }
+                _ => {
+                    match self.growth {
+                        Growth::Lzw => self.output_string(code),
+                        _ => self.output_phrase(code),
                     }
                 }
             }
         }  
     }
     fn output_string(&mut self, code: u32) {
-        let string = self.dict.get(code);
-
-        if string.is_some() {
-            let string = string.unwrap().to_vec();
+        if let Some(string) = self.dict.get(code) {
+            let string = string.to_vec();
 
             if !self.string.is_empty() {
                 self.string.push(string[0]);
@@ -130,14 +129,47 @@ impl Decoder {
             self.string = string.to_vec();
         }
     }
+    // Mirror the encoder's LZMW or LZAP dictionary growth, adding strings 
+    // built from the previous and current match.
+    fn output_phrase(&mut self, code: u32) {
+        if let Some(string) = self.dict.get(code) {
+            let string = string.to_vec();
+
+            if !self.string.is_empty() {
+                for new in new_strings(self.growth, &self.string, &string) {
+                    if self.dict.code as usize >= self.dict.codes.len() {
+                        break;
+                    }
+                    self.dict.insert(&new, self.dict.code);
+                }
+            }
+
+            for byte in string.iter() {
+                self.blk.push(*byte);
+            }
+
+            self.string = string;
+        }
+    }
+}
+
+pub fn decompress(blk_in: Vec<u8>, mem: usize, growth: Growth) -> Vec<u8> {
+    if blk_in.is_empty() { 
+        return Vec::new(); 
+    }
+    
+    let mut dec = Decoder::new(mem, growth);
+    dec.decompress(CodeReader::new(blk_in));
+    dec.blk
 }
 
-pub fn decompress(blk_in: Vec<u8>, mem: usize) -> Vec<u8> {
+/// Decompress a block compressed with entropy coded LZW.
+pub fn decompress_ec(blk_in: Vec<u8>, mem: usize, growth: Growth) -> Vec<u8> {
     if blk_in.is_empty() { 
         return Vec::new(); 
     }
     
-    let mut dec = Decoder::new(mem);
-    dec.decompress(blk_in);
+    let mut dec = Decoder::new(mem, growth);
+    dec.decompress(EntropyCodeReader::new(blk_in));
     dec.blk
 }
\ No newline at end of file
@@ -1,9 +1,15 @@
-use crate::lzw::{
-    code::CodeWriter,
-    constant::{
-        DATA_END,
-        LEN_UP,
-        RESET,
+use crate::{
+    config::Growth,
+    lzw::{
+        code::{CodeWrite, CodeWriter},
+        entropy::EntropyCodeWriter,
+        growth::new_strings,
+        constant::{
+            DATA_END,
+            LEN_UP,
+            RESET,
+            MAX_STR,
+        },
     },
 };
 
@@ -30,8 +36,8 @@ impl Dictionary {
     fn hash(&self, string: &[u8]) -> usize {
         let mut hash = 2166136261usize;
         for s in string.iter() {
-            hash = hash * 16777619;
-            hash = hash ^ *s as usize;
+            hash = hash.wrapping_mul(16777619);
+            hash ^= *s as usize;
         }
         hash & (self.codes.len() - 1)
     }
@@ -49,6 +55,15 @@ impl Dictionary {
         self.insert(string.to_vec(), hash);
         None
     }
+    // Look up a string without inserting it on a miss.
+    fn find(&self, string: &[u8]) -> Option<u32> {
+        let hash = self.hash(string);
+
+        if self.codes[hash] != 0 && self.strings[hash] == string {
+            return Some(self.codes[hash]);
+        }
+        None
+    }
     // Insert a new key-value pair into hash table if selected slot is 
     // empty, or if it is not empty but doesn't contain any strings of 
     // length 1. Because a new unseen string added to the dictionary may 
@@ -100,24 +115,30 @@ impl Dictionary {
 struct Encoder {
     dict:   Dictionary,
     string: Vec<u8>,
+    growth: Growth,
 }
 impl Encoder {
-    fn new(mem: usize) -> Encoder {
+    fn new(mem: usize, growth: Growth) -> Encoder {
         Encoder {
             dict:   Dictionary::new(mem/4),
             string: Vec::new(),
+            growth,
         }
     }
-    fn compress(&mut self, blk_in: Vec<u8>) -> CodeWriter {
-        let mut stream = CodeWriter::new();
-
+    fn compress<W: CodeWrite>(&mut self, blk_in: Vec<u8>, stream: W) -> Vec<u8> {
+        match self.growth {
+            Growth::Lzw => self.compress_lzw(blk_in, stream),
+            _ => self.compress_phrases(blk_in, stream),
+        }
+    }
+    fn compress_lzw<W: CodeWrite>(&mut self, blk_in: Vec<u8>, mut stream: W) -> Vec<u8> {
         for byte in blk_in.iter() {
             self.string.push(*byte);
 
             if self.dict.get(&self.string).is_none() {
                 stream.write(self.output_code());
 
-                if self.dict.code == 1 << stream.code_len {
+                if self.dict.code == 1 << stream.code_len() {
                     stream.write(LEN_UP);
                 }
         
@@ -134,7 +155,55 @@ impl Encoder {
             );
         }
         stream.write(DATA_END);
-        stream
+        stream.finish()
+    }
+    // LZMW and LZAP dictionaries aren't prefix closed, so rather than 
+    // extending the current string a byte at a time, search down from the 
+    // longest possible match at each position. New strings are built only 
+    // from whole matches the decoder has already seen, so unlike LZW there 
+    // is no case where a code is output before its string is known.
+    fn compress_phrases<W: CodeWrite>(&mut self, blk_in: Vec<u8>, mut stream: W) -> Vec<u8> {
+        let mut prev = 0..0;
+        let mut pos = 0;
+
+        while pos < blk_in.len() {
+            let max = MAX_STR.min(blk_in.len() - pos);
+
+            // Strings of length 1 are always present
+            let (len, code) = (1..=max).rev()
+                .find_map(|len| {
+                    self.dict.find(&blk_in[pos..pos+len]).map(|code| (len, code))
+                })
+                .unwrap();
+
+            stream.write(code);
+
+            let curr = pos..pos+len;
+            if !prev.is_empty() {
+                for string in new_strings(self.growth, &blk_in[prev], &blk_in[curr.clone()]) {
+                    if self.dict.code >= self.dict.max_code {
+                        break;
+                    }
+                    let hash = self.dict.hash(&string);
+                    self.dict.insert(string, hash);
+                }
+            }
+            prev = curr;
+            pos += len;
+
+            // LZAP can add several strings per match
+            while self.dict.code >= 1 << stream.code_len() {
+                stream.write(LEN_UP);
+            }
+
+            if self.dict.code >= self.dict.max_code {
+                stream.write(RESET);
+                self.dict.reset();
+                prev = 0..0;
+            }
+        }
+        stream.write(DATA_END);
+        stream.finish()
     }
     fn output_code(&mut self) -> u32 {
         let last_char = self.string.pop().unwrap();
@@ -148,9 +217,18 @@ impl Encoder {
 }
 
 
-pub fn compress(blk_in: Vec<u8>, mem: usize) -> Vec<u8> {
+pub fn compress(blk_in: Vec<u8>, mem: usize, growth: Growth) -> Vec<u8> {
+    if blk_in.is_empty() {
+        return Vec::new();
+    }
+    Encoder::new(mem, growth).compress(blk_in, CodeWriter::new())
+}
+
+/// Compress a block using LZW, entropy coding the codes instead of 
+/// packing them.
+pub fn compress_ec(blk_in: Vec<u8>, mem: usize, growth: Growth) -> Vec<u8> {
     if blk_in.is_empty() {
         return Vec::new();
     }
-    Encoder::new(mem).compress(blk_in).out
+    Encoder::new(mem, growth).compress(blk_in, EntropyCodeWriter::new())
 }
\ No newline at end of file
@@ -0,0 +1,130 @@
+use crate::{
+    cm::coder::{Encoder, Decoder, Model},
+    lzw::{
+        code::{CodeRead, CodeWrite},
+        constant::{DATA_END, LEN_UP, RESET},
+    },
+};
+
+const TREE_BITS: u32 = 12;  // Leading bits of a code modeled as a tree
+const MAX_LEN:   u32 = 32;  // Maximum code length
+
+/// Adaptive models for LZW codes. The leading TREE_BITS bits of a code 
+/// are modeled with a binary tree, so frequent codes are learned exactly, 
+/// and the remaining bits are modeled by position. Models are kept for 
+/// each code length since the distribution of codes changes as the 
+/// dictionary grows.
+struct CodeModel {
+    tree:  Vec<Model>,
+    low:   Vec<Model>,
+}
+impl CodeModel {
+    fn new() -> CodeModel {
+        CodeModel {
+            tree: vec![Model::default(); (MAX_LEN as usize + 1) << TREE_BITS],
+            low:  vec![Model::default(); (MAX_LEN as usize + 1) * MAX_LEN as usize],
+        }
+    }
+    fn encode(&mut self, enc: &mut Encoder, code: u32, code_len: u32) {
+        let top = code_len.min(TREE_BITS);
+        let rest = code_len - top;
+        let tree = &mut self.tree[(code_len as usize) << TREE_BITS..];
+        enc.encode_tree(tree, code >> rest, top);
+
+        let low = &mut self.low[code_len as usize * MAX_LEN as usize..];
+        for i in (0..rest).rev() {
+            enc.encode_model(((code >> i) & 1) as i32, &mut low[i as usize]);
+        }
+    }
+    fn decode(&mut self, dec: &mut Decoder, code_len: u32) -> u32 {
+        let top = code_len.min(TREE_BITS);
+        let rest = code_len - top;
+        let tree = &mut self.tree[(code_len as usize) << TREE_BITS..];
+        let mut code = dec.decode_tree(tree, top);
+
+        let low = &mut self.low[code_len as usize * MAX_LEN as usize..];
+        for i in (0..rest).rev() {
+            code = (code << 1) | dec.decode_model(&mut low[i as usize]) as u32;
+        }
+        code
+    }
+}
+
+/// Writes codes with adaptive arithmetic coding.
+pub struct EntropyCodeWriter {
+    enc:       Encoder,
+    model:     CodeModel,
+    code_len:  u32,
+}
+impl EntropyCodeWriter {
+    pub fn new() -> EntropyCodeWriter {
+        EntropyCodeWriter {
+            enc:       Encoder::new(),
+            model:     CodeModel::new(),
+            code_len:  9,
+        }
+    }
+}
+impl Default for EntropyCodeWriter {
+    fn default() -> EntropyCodeWriter {
+        EntropyCodeWriter::new()
+    }
+}
+impl CodeWrite for EntropyCodeWriter {
+    fn write(&mut self, code: u32) {
+        self.model.encode(&mut self.enc, code, self.code_len);
+        match code {
+            LEN_UP => {
+                self.code_len += 1;
+            }
+            DATA_END => {
+                self.enc.flush();
+            }
+            RESET => {
+                self.code_len = 9;
+            }
+            _ => {},
+        }
+    }
+    fn code_len(&self) -> u32 {
+        self.code_len
+    }
+    fn finish(self) -> Vec<u8> {
+        self.enc.out
+    }
+}
+
+/// Reads codes written by an EntropyCodeWriter.
+pub struct EntropyCodeReader {
+    dec:       Decoder,
+    model:     CodeModel,
+    code_len:  u32,
+}
+impl EntropyCodeReader {
+    pub fn new(blk_in: Vec<u8>) -> EntropyCodeReader {
+        EntropyCodeReader {
+            dec:       Decoder::new(blk_in),
+            model:     CodeModel::new(),
+            code_len:  9,
+        }
+    }
+}
+impl CodeRead for EntropyCodeReader {
+    fn get_code(&mut self) -> Option<u32> {
+        // Corrupted data can never reach DATA_END.
+        if self.code_len > MAX_LEN {
+            return None;
+        }
+        let code = self.model.decode(&mut self.dec, self.code_len);
+        match code {
+            LEN_UP => {
+                self.code_len += 1;
+            }
+            RESET => {
+                self.code_len = 9;
+            }
+            _ => {},
+        }
+        Some(code)
+    }
+}
@@ -0,0 +1,18 @@
+use crate::{
+    config::Growth,
+    lzw::constant::MAX_STR,
+};
+
+/// Return the strings added to the dictionary after matching 'curr' 
+/// directly after 'prev', in code order. Strings too long to store are 
+/// skipped and don't use a code.
+pub fn new_strings(growth: Growth, prev: &[u8], curr: &[u8]) -> Vec<Vec<u8>> {
+    let strings = match growth {
+        Growth::Lzw  => vec![[prev, &curr[..1]].concat()],
+        Growth::Lzmw => vec![[prev, curr].concat()],
+        Growth::Lzap => {
+            (1..=curr.len()).map(|len| [prev, &curr[..len]].concat()).collect()
+        }
+    };
+    strings.into_iter().filter(|s| s.len() <= MAX_STR).collect()
+}
@@ -35,24 +35,88 @@ fn main() {
                         print!("{err}");
                     } 
                 }
+                Mode::ExtractArchive if cfg.salvage => {
+                    match Prisirv::new(cfg).salvage_archive() {
+                        Ok(incomplete) if incomplete.is_empty() => {
+                            println!("All files found were extracted completely.");
+                        }
+                        Ok(incomplete) => {
+                            println!("Incomplete files:");
+                            for path in incomplete.iter() {
+                                println!("    {}", path.display());
+                            }
+                        }
+                        Err(err) => {
+                            print!("An error occurred while salvaging archive.");
+                            print!("{err}");
+                        }
+                    }
+                }
                 Mode::ExtractArchive => { 
                     if let Err(err) = Prisirv::new(cfg).extract_archive() {
                         print!("An error occurred while extracting archive.");
                         print!("{err}");
+                        std::process::exit(1);
                     } 
                 }
+                Mode::TestArchive => {
+                    match Prisirv::new(cfg).test_archive() {
+                        Ok(report) => {
+                            println!("{report}");
+                            if !report.passed() {
+                                std::process::exit(1);
+                            }
+                        }
+                        Err(err) => {
+                            print!("An error occurred while testing archive.");
+                            print!("{err}");
+                            std::process::exit(1);
+                        }
+                    }
+                }
                 Mode::AppendFiles => { 
                     if let Err(err) = Prisirv::new(cfg).append_files() {
                         print!("An error occurred while appending files.");
                         print!("{err}");
                     }
                 }
+                Mode::UpdateFiles => {
+                    match Prisirv::new(cfg).update_files() {
+                        Ok(paths) if paths.is_empty() => {
+                            println!("Archive is up to date.");
+                        }
+                        Ok(paths) => {
+                            println!("Appended {} new or changed files.", paths.len());
+                        }
+                        Err(err) => {
+                            print!("An error occurred while updating archive.");
+                            print!("{err}");
+                        }
+                    }
+                }
                 Mode::ExtractFiles => { 
                     if let Err(err) = Prisirv::new(cfg).extract_files() {
                         print!("An error occurred while extracting files.");
                         print!("{err}");
+                        std::process::exit(1);
                     }  
                 }
+                Mode::DeleteFiles => {
+                    match Prisirv::new(cfg).delete_files() {
+                        Ok(paths) if paths.is_empty() => {
+                            println!("No files matched.");
+                        }
+                        Ok(paths) => {
+                            for path in paths.iter() {
+                                println!("Deleted {}", path.display());
+                            }
+                        }
+                        Err(err) => {
+                            print!("An error occurred while deleting files.");
+                            print!("{err}");
+                        }
+                    }
+                }
                 Mode::MergeArchives => { 
                     if let Err(err) = Prisirv::new(cfg).merge_archives() {
                         print!("An error occurred while merging archives.");
@@ -73,6 +137,43 @@ fn main() {
                         Err(err) => println!("{err}"),
                     }
                 }
+                Mode::GenerateKeys => {
+                    if let Err(err) = Prisirv::new(cfg).generate_keys() {
+                        print!("An error occurred while generating keys.");
+                        print!("{err}");
+                    }
+                }
+                Mode::VerifySignature => {
+                    match Prisirv::new(cfg).verify_signature() {
+                        Ok(key) => {
+                            let key = key.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+                            println!("Signature is valid, signed by {key}.");
+                        }
+                        Err(err) => {
+                            print!("Signature verification failed.");
+                            print!("{err}");
+                            std::process::exit(1);
+                        }
+                    }
+                }
+                Mode::Repair => {
+                    match Prisirv::new(cfg).repair() {
+                        Ok(ranges) if ranges.is_empty() => {
+                            println!("No damage found.");
+                        }
+                        Ok(ranges) => {
+                            for range in ranges.iter() {
+                                println!("Rebuilt bytes {}..{}", range.start, range.end);
+                            }
+                            println!("Archive repaired.");
+                        }
+                        Err(err) => {
+                            print!("An error occurred while repairing archive.");
+                            print!("{err}");
+                            std::process::exit(1);
+                        }
+                    }
+                }
                 Mode::None => {
                     print!("{}", Prisirv::default());
                 }
@@ -24,7 +24,8 @@ impl Progress {
     pub fn new(cfg: &Config) -> Progress {
         let sizei = 
         if cfg.mode == Mode::ExtractArchive
-        || cfg.mode == Mode::ExtractFiles {
+        || cfg.mode == Mode::ExtractFiles
+        || cfg.mode == Mode::TestArchive {
             cfg.arch.len
         }
         else {
@@ -36,6 +37,7 @@ impl Progress {
         let sizeo =
         if cfg.mode == Mode::ExtractFiles
         || cfg.mode == Mode::ExtractArchive
+        || cfg.mode == Mode::TestArchive
         || cfg.mode == Mode::CreateArchive {
             0
         }
@@ -60,6 +62,7 @@ impl Progress {
         self.current += blk.sizei;
         if self.mode == Mode::CreateArchive
         || self.mode == Mode::AppendFiles
+        || self.mode == Mode::UpdateFiles
         || self.mode == Mode::MergeArchives {
             self.sizeo += blk.size();
         }
@@ -92,6 +95,11 @@ impl Drop for Progress {
                         self.sizeo, self.time.elapsed()
                     );
                 }
+                Mode::TestArchive => {
+                    println!("\rTested {} bytes in {:.2?}                                                      ", 
+                        self.sizeo, self.time.elapsed()
+                    );
+                }
                 _ => {
                     println!("\r                                                                                   
                         \rArchive size: {} bytes
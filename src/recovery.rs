@@ -0,0 +1,411 @@
+use std::{
+    io::{self, Read, Write, Seek, SeekFrom, BufReader, BufWriter},
+    ops::Range,
+    cmp::min,
+    convert::TryInto,
+};
+
+use reed_solomon_erasure::galois_8::ReedSolomon;
+
+use crate::{
+    filedata::FileData,
+    buffered_io::{BufferedRead, BufferedWrite},
+    error::ArchiveError,
+    constant::RECOVERY_MAGIC,
+    crc32::Crc32Stream,
+    volume::{self, Volumes},
+};
+
+// An archive can optionally end with a recovery record, Reed-Solomon
+// parity over everything before it, so damaged parts of the archive can
+// be rebuilt with 'repair'. The protected bytes are split into shards,
+// and shard i belongs to group i % groups, so a run of damaged bytes is
+// spread across groups instead of exhausting the parity of one group.
+// Each group has at most 255 data and parity shards, and the number of
+// parity shards is the redundancy percentage of its data shards:
+//
+//  RECOVERY_MAGIC                    u32
+//  Protected length                  u64
+//  Shard size                        u32
+//  Redundancy (percent)              u8
+//  For each data shard, then each parity shard:
+//      Shard CRC32                   u32
+//  Header CRC32                      u32
+//  Parity shards, by group
+//  Copy of the header, up to and including its CRC32
+//  Footer:
+//      Record position               u64
+//      RECOVERY_MAGIC                u32
+
+const MAX_SHARDS: usize = 255;
+const MIN_SHARD_SIZE: u64 = 1024;
+const MAX_DATA_SHARDS: u64 = 1 << 20;
+const RECORD_FOOTER_LEN: u64 = 12;
+
+/// Shard and group layout of a recovery record.
+struct Layout {
+    len:     u64,        // Protected length
+    shard:   u64,        // Shard size
+    pct:     u8,         // Redundancy
+    data:    usize,      // Number of data shards
+    groups:  usize,      // Number of groups
+    parity:  Vec<usize>, // First parity shard of each group, and total
+}
+impl Layout {
+    /// Layout for 'len' bytes with 'pct' percent redundancy, with shards
+    /// large enough to keep the number of shards bounded.
+    fn new(len: u64, pct: u8) -> Layout {
+        let shard = len.div_ceil(MAX_DATA_SHARDS).max(MIN_SHARD_SIZE);
+        Layout::with(len, shard, pct)
+    }
+    fn with(len: u64, shard: u64, pct: u8) -> Layout {
+        let data = len.div_ceil(shard).max(1) as usize;
+
+        // Most data shards a group can have with room for its parity
+        let max_data = (1..=MAX_SHARDS)
+            .rev()
+            .find(|d| d + parity_count(*d, pct) <= MAX_SHARDS)
+            .unwrap();
+        let groups = data.div_ceil(max_data);
+
+        let mut layout = Layout {
+            len, shard, pct, data, groups, parity: vec![0],
+        };
+        for g in 0..groups {
+            let count = parity_count(layout.data_shards(g).count(), pct);
+            layout.parity.push(layout.parity[g] + count);
+        }
+        layout
+    }
+    /// Data shards in group 'g'.
+    fn data_shards(&self, g: usize) -> impl Iterator<Item = usize> {
+        (g..self.data).step_by(self.groups)
+    }
+    /// Parity shards in group 'g'.
+    fn parity_shards(&self, g: usize) -> Range<usize> {
+        self.parity[g]..self.parity[g + 1]
+    }
+    fn parity_total(&self) -> usize {
+        self.parity[self.groups]
+    }
+    /// Archive range of data shard 'i', shorter than a shard if it's the last.
+    fn data_range(&self, i: usize) -> Range<u64> {
+        let beg = i as u64 * self.shard;
+        beg..min(beg + self.shard, self.len)
+    }
+    /// Archive range of parity shard 'i', in a record at 'pos'.
+    fn parity_range(&self, pos: u64, i: usize) -> Range<u64> {
+        let beg = pos + self.header_len() + i as u64 * self.shard;
+        beg..beg + self.shard
+    }
+    fn header_len(&self) -> u64 {
+        4 + 8 + 4 + 1 + 4 * (self.data + self.parity_total()) as u64 + 4
+    }
+    /// Position of the copy of the header, in a record at 'pos'.
+    fn copy_pos(&self, pos: u64) -> u64 {
+        pos + self.header_len() + self.parity_total() as u64 * self.shard
+    }
+    /// Size of the whole record, including the footer.
+    fn record_len(&self) -> u64 {
+        self.copy_pos(0) + self.header_len() + RECORD_FOOTER_LEN
+    }
+}
+
+fn parity_count(data: usize, pct: u8) -> usize {
+    (data * pct as usize).div_ceil(100).max(1)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32Stream::default();
+    crc.update(data);
+    crc.finish()
+}
+
+/// Reed-Solomon codecs by group shape. Groups differ by at most one data
+/// shard and building a codec is expensive, so few are needed.
+#[derive(Default)]
+struct Codecs {
+    codecs: Vec<ReedSolomon>,
+}
+impl Codecs {
+    fn get(&mut self, data: usize, parity: usize) -> &ReedSolomon {
+        let pos = self.codecs.iter().position(|rs| {
+            rs.data_shard_count() == data && rs.parity_shard_count() == parity
+        });
+        match pos {
+            Some(i) => &self.codecs[i],
+            None => {
+                self.codecs.push(ReedSolomon::new(data, parity).unwrap());
+                self.codecs.last().unwrap()
+            }
+        }
+    }
+}
+
+/// A recovery record header, with the CRC32 of every shard.
+struct Record {
+    layout:  Layout,
+    crcs:    Vec<u32>, // Data shard CRCs, then parity shard CRCs
+}
+impl Record {
+    fn write_to<W: Write>(&self, archive: &mut BufWriter<W>) {
+        let mut header = BufWriter::new(Vec::new());
+        header.write_u32(RECOVERY_MAGIC);
+        header.write_u64(self.layout.len);
+        header.write_u32(self.layout.shard as u32);
+        header.write_byte(self.layout.pct);
+        for crc in self.crcs.iter() {
+            header.write_u32(*crc);
+        }
+        let header = header.into_inner().unwrap();
+        archive.write_all(&header).unwrap();
+        archive.write_u32(crc32(&header));
+    }
+    /// Write both copies of the header and the footer of a record at 'pos'.
+    /// The copy is written first, as it extends the archive past the 
+    /// parity shards.
+    fn write_headers<W: Write + Seek>(&self, archive: &mut W, pos: u64) -> io::Result<()> {
+        let mut header = BufWriter::new(Vec::new());
+        self.write_to(&mut header);
+        let header = header.into_inner().unwrap();
+
+        archive.seek(SeekFrom::Start(self.layout.copy_pos(pos)))?;
+        archive.write_all(&header)?;
+        archive.write_all(&pos.to_le_bytes())?;
+        archive.write_all(&RECOVERY_MAGIC.to_le_bytes())?;
+
+        archive.seek(SeekFrom::Start(pos))?;
+        archive.write_all(&header)
+    }
+    /// Read a record header at the current position, if it's intact.
+    fn read_from<R: Read>(archive: &mut BufReader<R>) -> Option<Record> {
+        let mut fields = [0u8; 17];
+        archive.read_exact(&mut fields).ok()?;
+        if fields[..4] != RECOVERY_MAGIC.to_le_bytes() {
+            return None;
+        }
+        let len   = u64::from_le_bytes(fields[4..12].try_into().unwrap());
+        let shard = u32::from_le_bytes(fields[12..16].try_into().unwrap()) as u64;
+        let pct   = fields[16];
+        if shard == 0 || !(1..=100).contains(&pct) || len / shard > MAX_DATA_SHARDS {
+            return None;
+        }
+
+        let layout = Layout::with(len, shard, pct);
+        let mut crcs = vec![0u8; 4 * (layout.data + layout.parity_total())];
+        archive.read_exact(&mut crcs).ok()?;
+
+        let mut crc = Crc32Stream::default();
+        crc.update(&fields);
+        crc.update(&crcs);
+        if archive.read_u32() != crc.finish() {
+            return None;
+        }
+        Some(
+            Record {
+                crcs: crcs.chunks_exact(4)
+                    .map(|crc| u32::from_le_bytes(crc.try_into().unwrap()))
+                    .collect(),
+                layout,
+            }
+        )
+    }
+    /// Read the recovery record of an archive and its position, using
+    /// the footer.
+    fn locate<R: Read + Seek>(archive: &mut BufReader<R>) -> Result<Option<(u64, Record)>, ArchiveError> {
+        if let Some(pos) = record_pos(archive)? {
+            archive.seek(SeekFrom::Start(pos))?;
+            if let Some(record) = Record::read_from(archive) {
+                if record.layout.len == pos {
+                    return Ok(Some((pos, record)));
+                }
+            }
+        }
+        Ok(None)
+    }
+    /// Find the recovery record of an archive and its position, searching
+    /// backwards from the end for either copy of the header if the footer
+    /// or the first header is damaged.
+    fn find<R: Read + Seek>(archive: &mut BufReader<R>) -> Result<Option<(u64, Record)>, ArchiveError> {
+        if let Some(found) = Record::locate(archive)? {
+            return Ok(Some(found));
+        }
+        let len = archive.seek(SeekFrom::End(0))?;
+
+        let magic = RECOVERY_MAGIC.to_le_bytes();
+        let mut buf = vec![0u8; 1 << 16];
+        let mut end = len;
+        while end > 0 {
+            // Overlap chunks so a magic number can't be split between them
+            let beg = end.saturating_sub(buf.len() as u64);
+            let chunk = &mut buf[..(end - beg) as usize];
+            archive.seek(SeekFrom::Start(beg))?;
+            archive.read_exact(chunk)?;
+
+            let candidates = chunk.windows(4)
+                .enumerate()
+                .rev()
+                .filter(|(_, window)| *window == magic)
+                .map(|(i, _)| beg + i as u64)
+                .collect::<Vec<u64>>();
+
+            for candidate in candidates {
+                archive.seek(SeekFrom::Start(candidate))?;
+                if let Some(record) = Record::read_from(archive) {
+                    // The record starts at the end of the protected bytes
+                    let pos = record.layout.len;
+                    let found = candidate == pos || candidate == record.layout.copy_pos(pos);
+                    if found && pos + record.layout.record_len() <= len {
+                        return Ok(Some((pos, record)));
+                    }
+                }
+            }
+            end = if beg == 0 { 0 } else { beg + 3 };
+        }
+        Ok(None)
+    }
+}
+
+/// Position of an archive's recovery record, if its footer is intact.
+pub fn record_pos<R: Read + Seek>(archive: &mut BufReader<R>) -> Result<Option<u64>, ArchiveError> {
+    let len = archive.seek(SeekFrom::End(0))?;
+    if len < RECORD_FOOTER_LEN {
+        return Ok(None);
+    }
+    archive.seek(SeekFrom::End(-(RECORD_FOOTER_LEN as i64)))?;
+    let pos   = archive.read_u64();
+    let magic = archive.read_u32();
+    if magic != RECOVERY_MAGIC || pos >= len {
+        return Ok(None);
+    }
+    Ok(Some(pos))
+}
+
+/// Redundancy of an existing archive's recovery record, if it has one.
+pub fn redundancy(arch: &FileData) -> Result<Option<u8>, ArchiveError> {
+    if arch.new || !volume::exists(&arch.path) {
+        return Ok(None);
+    }
+    let mut archive = BufReader::new(Volumes::open(&arch.path)?);
+    Ok(Record::locate(&mut archive)?.map(|(_, record)| record.layout.pct))
+}
+
+/// Read data shard 'i', padding the last shard with zeros.
+fn read_data_shard<R: Read + Seek>(archive: &mut R, layout: &Layout, i: usize, shard: &mut [u8]) -> io::Result<()> {
+    let range = layout.data_range(i);
+    shard.fill(0);
+    archive.seek(SeekFrom::Start(range.start))?;
+    archive.read_exact(&mut shard[..(range.end - range.start) as usize])
+}
+
+/// Append a recovery record with 'pct' percent redundancy, protecting
+/// everything in the archive.
+pub fn write_record<F: Read + Write + Seek>(archive: &mut F, pct: u8) -> Result<(), ArchiveError> {
+    let pos = archive.seek(SeekFrom::End(0))?;
+    let layout = Layout::new(pos, pct);
+    let mut crcs = vec![0u32; layout.data + layout.parity_total()];
+    let mut shard = vec![0u8; layout.shard as usize];
+    let mut codecs = Codecs::default();
+
+    // Headers are written once all CRCs are known. The record is written
+    // in order, as volumes can't be written past their end.
+    archive.write_all(&vec![0u8; layout.header_len() as usize])?;
+
+    for g in 0..layout.groups {
+        let data_shards = layout.data_shards(g).collect::<Vec<usize>>();
+        let parity_shards = layout.parity_shards(g);
+        let rs = codecs.get(data_shards.len(), parity_shards.len());
+        let mut parity = vec![vec![0u8; layout.shard as usize]; parity_shards.len()];
+
+        for (n, i) in data_shards.into_iter().enumerate() {
+            read_data_shard(archive, &layout, i, &mut shard)?;
+            crcs[i] = crc32(&shard);
+            rs.encode_single_sep(n, &shard, &mut parity).unwrap();
+        }
+        for (i, shard) in parity_shards.zip(parity.iter()) {
+            crcs[layout.data + i] = crc32(shard);
+            archive.seek(SeekFrom::Start(layout.parity_range(pos, i).start))?;
+            archive.write_all(shard)?;
+        }
+    }
+
+    let record = Record { layout, crcs };
+    record.write_headers(archive, pos)?;
+    archive.flush()?;
+    Ok(())
+}
+
+/// Rebuild damaged parts of an archive from its recovery record,
+/// returning the archive ranges that were rebuilt.
+pub fn repair(arch: &FileData) -> Result<Vec<Range<u64>>, ArchiveError> {
+    if !volume::exists(&arch.path) {
+        return Err(ArchiveError::FileNotFound(arch.path.clone()));
+    }
+    let mut archive = BufReader::new(Volumes::create(arch, None, true)?);
+    let (pos, record) = Record::find(&mut archive)?.ok_or(ArchiveError::RecoveryRecordMissing)?;
+    let layout = &record.layout;
+    let mut archive = archive.into_inner();
+
+    let mut codecs = Codecs::default();
+    let mut repaired: Vec<Range<u64>> = Vec::new();
+    let mut lost = 0;
+
+    for g in 0..layout.groups {
+        // Shard ranges in the archive and their expected CRCs, data first
+        let ranges = layout.data_shards(g)
+            .map(|i| (layout.data_range(i), record.crcs[i]))
+            .chain(layout.parity_shards(g).map(|i| {
+                (layout.parity_range(pos, i), record.crcs[layout.data + i])
+            }))
+            .collect::<Vec<(Range<u64>, u32)>>();
+
+        let mut shards = Vec::with_capacity(ranges.len());
+        for (range, crc) in ranges.iter() {
+            let mut shard = vec![0u8; layout.shard as usize];
+            archive.seek(SeekFrom::Start(range.start))?;
+            let intact = archive.read_exact(&mut shard[..(range.end - range.start) as usize]).is_ok()
+                && crc32(&shard) == *crc;
+            shards.push(intact.then_some(shard));
+        }
+        let damaged = shards.iter().filter(|shard| shard.is_none()).count();
+        if damaged == 0 {
+            continue;
+        }
+
+        let data_count = layout.data_shards(g).count();
+        let rs = codecs.get(data_count, ranges.len() - data_count);
+        let missing = shards.iter().map(|shard| shard.is_none()).collect::<Vec<bool>>();
+        if rs.reconstruct(&mut shards).is_err() {
+            lost += damaged;
+            continue;
+        }
+
+        for (((range, _), shard), missing) in ranges.iter().zip(shards.iter()).zip(missing) {
+            if missing {
+                let shard = shard.as_ref().unwrap();
+                archive.seek(SeekFrom::Start(range.start))?;
+                archive.write_all(&shard[..(range.end - range.start) as usize])?;
+                repaired.push(range.clone());
+            }
+        }
+    }
+
+    // Either header or the footer may have been damaged
+    record.write_headers(&mut archive, pos)?;
+    archive.flush()?;
+
+    if lost > 0 {
+        return Err(ArchiveError::RepairFailed(lost));
+    }
+
+    // Merge adjacent ranges
+    repaired.sort_by_key(|range| range.start);
+    let mut merged: Vec<Range<u64>> = Vec::new();
+    for range in repaired {
+        match merged.last_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ => merged.push(range),
+        }
+    }
+    Ok(merged)
+}
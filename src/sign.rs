@@ -0,0 +1,140 @@
+use std::{
+    io::{self, Read, Write, Seek, SeekFrom, BufReader, BufWriter},
+    fs,
+    path::{Path, PathBuf},
+    convert::TryInto,
+};
+
+use ed25519_dalek::{Signature, Signer as _, SigningKey, VerifyingKey};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    block::Block,
+    archiveinfo::ArchiveInfo,
+    buffered_io::BufferedWrite,
+    error::ArchiveError,
+    constant::SIG_MAGIC,
+    crypto::hex,
+};
+
+// A signed archive has an Ed25519 signature over the SHA-256 hash of all
+// blocks, including the sentinel block. The signature is either embedded
+// in a trailer written directly after the sentinel block, before any
+// index, or written to a detached file next to the archive:
+//
+//  SIG_MAGIC                   u32
+//  Signer's public key         32 bytes
+//  Signature                   64 bytes
+
+pub const TRAILER_LEN: u64 = 4 + 32 + 64;
+
+const CONTEXT: &[u8] = b"prisirv archive signature";
+
+/// Hashes blocks as they are written and signs the final hash.
+pub struct Signer {
+    key:     SigningKey,
+    hasher:  Sha256,
+}
+impl Signer {
+    pub fn new(secret: &[u8; 32]) -> Signer {
+        Signer {
+            key:     SigningKey::from_bytes(secret),
+            hasher:  Sha256::new(),
+        }
+    }
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.hasher.update(bytes);
+    }
+    /// Hash the first 'len' bytes of an existing archive.
+    pub fn update_from<R: Read>(&mut self, archive: &mut BufReader<R>, len: u64) -> Result<(), ArchiveError> {
+        io::copy(&mut archive.by_ref().take(len), &mut self.hasher)?;
+        Ok(())
+    }
+    pub fn finish(self) -> Trailer {
+        let msg = [CONTEXT, &self.hasher.finalize()].concat();
+        Trailer {
+            key: self.key.verifying_key().to_bytes(),
+            sig: self.key.sign(&msg).to_bytes(),
+        }
+    }
+}
+
+/// A signature and the public key of its signer.
+pub struct Trailer {
+    pub key:  [u8; 32],
+    pub sig:  [u8; 64],
+}
+impl Trailer {
+    pub fn write_to<W: Write>(&self, archive: &mut BufWriter<W>) {
+        archive.write_u32(SIG_MAGIC);
+        archive.write_all(&self.key).unwrap();
+        archive.write_all(&self.sig).unwrap();
+    }
+    /// Read a signature, if one is present.
+    pub fn read_from<R: Read>(archive: &mut BufReader<R>) -> Option<Trailer> {
+        let mut trailer = [0u8; TRAILER_LEN as usize];
+        archive.read_exact(&mut trailer).ok()?;
+        if trailer[..4] != SIG_MAGIC.to_le_bytes() {
+            return None;
+        }
+        Some(
+            Trailer {
+                key: trailer[4..36].try_into().unwrap(),
+                sig: trailer[36..].try_into().unwrap(),
+            }
+        )
+    }
+    /// Write a detached signature file.
+    pub fn write_detached(&self, path: &Path) -> Result<(), ArchiveError> {
+        let mut file = BufWriter::new(fs::File::create(path)?);
+        self.write_to(&mut file);
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// Path of the detached signature of an archive.
+pub fn detached_path(arch: &Path) -> PathBuf {
+    let mut path = arch.as_os_str().to_owned();
+    path.push(".sig");
+    PathBuf::from(path)
+}
+
+/// Verify the signature of an archive, using the detached signature
+/// file 'detached' if it exists, or the embedded trailer otherwise. If
+/// any signers are given, the archive must be signed by one of them.
+/// Returns the signer's public key.
+pub fn verify<R: Read + Seek>(archive: &mut BufReader<R>, detached: Option<&Path>, signers: &[[u8; 32]]) -> Result<[u8; 32], ArchiveError> {
+    // Signed data ends after the sentinel block, found from the block 
+    // headers, as the index isn't signed
+    let eod = ArchiveInfo::from_headers(archive)?.end_of_data();
+    let mut sentinel = Block::default();
+    archive.seek(SeekFrom::Start(eod))?;
+    sentinel.read_header_from(archive)?;
+    let end = eod + sentinel.size();
+
+    let trailer = match detached {
+        Some(path) if path.exists() => {
+            Trailer::read_from(&mut BufReader::new(fs::File::open(path)?))
+        }
+        _ => {
+            archive.seek(SeekFrom::Start(end))?;
+            Trailer::read_from(archive)
+        }
+    };
+    let trailer = trailer.ok_or(ArchiveError::SignatureMissing)?;
+
+    let mut hasher = Sha256::new();
+    archive.rewind()?;
+    io::copy(&mut archive.by_ref().take(end), &mut hasher)?;
+    let msg = [CONTEXT, &hasher.finalize()].concat();
+
+    VerifyingKey::from_bytes(&trailer.key)
+        .and_then(|key| key.verify_strict(&msg, &Signature::from_bytes(&trailer.sig)))
+        .map_err(|_| ArchiveError::InvalidSignature)?;
+
+    if !signers.is_empty() && !signers.contains(&trailer.key) {
+        return Err(ArchiveError::UntrustedSigner(hex(&trailer.key)));
+    }
+    Ok(trailer.key)
+}
@@ -9,7 +9,7 @@ use crate::{
     filedata::{FileData, Type},
 };
 
-/// Sort files to improve compression of solid archives.
+// Sort files to improve compression of solid archives.
 
 /// Possible sorting methods.
 #[derive(Debug, Clone, Copy)]
@@ -1,7 +1,8 @@
 use std::{
     time::SystemTime,
     cmp::Ordering,
-    collections::BinaryHeap,
+    collections::{BinaryHeap, BTreeMap},
+    panic::{self, AssertUnwindSafe},
     thread::{self, JoinHandle},
     sync::{
         mpsc::{self, Sender, Receiver},
@@ -12,15 +13,17 @@ use crate::{
     progress::Progress,
     crc32::Crc32,
     block::Block,
-    config::{Config, Method},
+    config::{Config, Method, Filter},
     error::ArchiveError,
     constant::Version,
-    lzw
+    crypto::Keyring,
+    lzw, cm, lz77, bwt, filter,
 };
 
+/// A task to compress or decompress the block with the given id.
 pub enum Task {
-    Compress(B),
-    Decompress(B),
+    Compress(u32, B),
+    Decompress(u32, B),
     Terminate,
 }
 
@@ -34,9 +37,11 @@ type SharedProgress   = Arc<Mutex<Progress>>;
 /// tasks to idle threads, where a task is a function that returns a
 /// compressed or decompressed block.
 pub struct ThreadPool {
-    threads:  Vec<Thread>,
-    sndr:     Sender<Task>,
-    pub bq:   SharedBlockQueue,
+    threads:   Vec<Thread>,
+    sndr:      Sender<Task>,
+    min_gain:  Option<u64>, // Store blocks under this gain (%), if set
+    keyring:   Keyring,     // Keys for encrypting or decrypting blocks
+    pub bq:    SharedBlockQueue,
 }
 impl ThreadPool {
     /// Create a new ThreadPool.
@@ -58,68 +63,145 @@ impl ThreadPool {
             );
         }
         ThreadPool { 
-            threads, sndr, bq
+            threads, sndr, bq,
+            min_gain: cfg.auto.then_some(cfg.min_gain),
+            keyring:  Keyring::new(cfg),
         }
     }
 
     /// Create a new task consisting of compressing an
-    /// input block and returning the compressed block.
-    pub fn compress_block(&mut self, blk_in: Block) {
+    /// input block and returning the compressed block, encrypted if a
+    /// password was given.
+    pub fn compress_block(&mut self, blk_in: Block) -> Result<(), ArchiveError> {
         let mem = blk_in.mem as usize;
+        let min_gain = self.min_gain;
+
+        // The sentinel block is never encrypted, and blocks of versions
+        // before 0.9 have no field to store encryption parameters in
+        let key = match blk_in.is_empty() || !blk_in.ver.has(Version::CRYPT) {
+            true => None,
+            false => self.keyring.encryption_key()?,
+        };
         
         self.sndr.send(
             Task::Compress(
+                blk_in.id,
                 Box::new(move || {
                     let chksum = (&blk_in.data).crc32();
                     let sizei = blk_in.data.len() as u64;
+                    let mut method = blk_in.method;
+                    let mut filter = filter::resolve(blk_in.filter, &blk_in.data);
 
-                    let blk_out = match blk_in.method {
+                    // Keep a copy of the input in case compression doesn't pay off
+                    let fallback = match min_gain {
+                        Some(gain) if method != Method::Store => {
+                            Some((gain, blk_in.data.clone()))
+                        }
+                        _ => None,
+                    };
+
+                    let data = filter::encode(filter, blk_in.data);
+
+                    let mut blk_out = match blk_in.method {
                         Method::Lzw => {
-                            lzw::encoder::compress(blk_in.data, mem)
+                            lzw::encoder::compress(data, mem, blk_in.growth)
+                        }
+                        Method::Cm => {
+                            cm::encoder::compress(data, mem)
+                        }
+                        Method::Lz77 => {
+                            lz77::encoder::compress(data, mem)
+                        }
+                        Method::Bwt => {
+                            bwt::encoder::compress(data)
+                        }
+                        Method::LzwEc => {
+                            lzw::encoder::compress_ec(data, mem, blk_in.growth)
                         }
                         Method::Store => {
-                            blk_in.data
+                            data
                         }
                     };
+
+                    if let Some((gain, data)) = fallback {
+                        if blk_out.len() as u64 * 100 >= sizei * (100 - gain) {
+                            blk_out = data;
+                            method = Method::Store;
+                            filter = Filter::None;
+                        }
+                    }
                     
                     let crtd = SystemTime::now()
                         .duration_since(SystemTime::UNIX_EPOCH)?
-                        .as_secs() as u64;
+                        .as_secs();
 
-                    Ok(
-                        Block {
-                            sizeo:  blk_out.len() as u64,
-                            data:   blk_out,
-                            chksum,
-                            sizei, 
-                            crtd,
-                            ..blk_in
-                        }
-                    )
+                    let mut blk_out = Block {
+                        sizeo:  blk_out.len() as u64,
+                        data:   blk_out,
+                        chksum,
+                        sizei, 
+                        crtd,
+                        method,
+                        filter,
+                        ..blk_in
+                    };
+                    if let Some((key, crypt)) = key {
+                        blk_out.encrypt(&key, crypt);
+                    }
+                    Ok(blk_out)
                 })
             )
         ).unwrap();
+        Ok(())
+    }
+
+    /// Queue an already compressed block to be returned in order with
+    /// blocks compressed by the pool.
+    pub fn copy_block(&mut self, blk: Block) {
+        self.bq.lock().unwrap().blocks.push(blk);
     }
 
     /// Create a new task containing a job consisting of decompressing
-    /// an input block and returning the decompressed block.
-    pub fn decompress_block(&mut self, blk_in: Block) -> Result<(), ArchiveError> {
-        if blk_in.ver != Version::current() {
+    /// an input block and returning the decompressed block, decrypting 
+    /// it first if it is encrypted.
+    pub fn decompress_block(&mut self, mut blk_in: Block) -> Result<(), ArchiveError> {
+        if !blk_in.ver.readable() {
             return Err(ArchiveError::InvalidVersion(blk_in.ver));
         }
-        let len = blk_in.data.len();
+        let key = match &blk_in.crypt {
+            Some(crypt) => Some(self.keyring.key(crypt)?),
+            None => None,
+        };
         let mem = blk_in.mem as usize; 
         self.sndr.send(
             Task::Decompress(
+                blk_in.id,
                 Box::new(move || {
+                    if let Some(key) = key {
+                        blk_in.decrypt(&key)?;
+                    }
+                    let len = blk_in.data.len();
                     let blk_out = match blk_in.method {
                         Method::Lzw => {
-                            lzw::decoder::decompress(blk_in.data, mem)
+                            lzw::decoder::decompress(blk_in.data, mem, blk_in.growth)
+                        }
+                        Method::Cm => {
+                            cm::decoder::decompress(blk_in.data, mem, filter::max_len(blk_in.sizei as usize))
+                        }
+                        Method::Lz77 => {
+                            lz77::decoder::decompress(blk_in.data, filter::max_len(blk_in.sizei as usize))
+                        }
+                        Method::Bwt => {
+                            bwt::decoder::decompress(blk_in.data, filter::max_len(blk_in.sizei as usize))
+                        }
+                        Method::LzwEc => {
+                            lzw::decoder::decompress_ec(blk_in.data, mem, blk_in.growth)
                         }
                         Method::Store => {
                             blk_in.data
                         }
                     };
+                    let blk_out = filter::decode(blk_in.filter, blk_out);
                     
                     let chksum = (&blk_out).crc32();
                     if chksum != blk_in.chksum {
@@ -174,22 +256,20 @@ impl Thread {
             let task = rcvr.lock().unwrap().recv().unwrap();
 
             match task {
-                Task::Compress(job) => {
-                    if let Ok(blk) = job() {
-                        prg.lock().unwrap().update(&blk);
-                        bq.lock().unwrap().blocks.push(blk);
-                    }
-                    else {
-                        break;
-                    }
-                }
-                Task::Decompress(job) => {
-                    if let Ok(blk) = job() {
-                        prg.lock().unwrap().update(&blk);
-                        bq.lock().unwrap().blocks.push(blk);
-                    }
-                    else {
-                        break;
+                Task::Compress(id, job) | Task::Decompress(id, job) => {
+                    // Decoders can panic on damaged data, which must not 
+                    // leave the block queue waiting for this block forever
+                    let result = panic::catch_unwind(AssertUnwindSafe(job))
+                        .unwrap_or(Err(ArchiveError::IncorrectChecksum(id)));
+
+                    match result {
+                        Ok(blk) => {
+                            prg.lock().unwrap().update(&blk);
+                            bq.lock().unwrap().blocks.push(blk);
+                        }
+                        Err(err) => {
+                            bq.lock().unwrap().failed.insert(id, err);
+                        }
                     }
                 }
                 Task::Terminate => {
@@ -212,6 +292,7 @@ pub struct BlockQueue {
     pub blocks:   BinaryHeap<Block>, // Priority Queue based on block id
     pub offset:   u32, // Starting id (non zero when appending to archive)
     pub next_out: u32, // Next block to be output
+    pub failed:   BTreeMap<u32, ArchiveError>, // Errors of failed jobs, by block id
 }
 impl BlockQueue {
     /// Create a new BlockQueue.
@@ -220,18 +301,35 @@ impl BlockQueue {
             blocks:    BinaryHeap::new(),
             offset:    start,
             next_out:  0,
+            failed:    BTreeMap::new(),
         }
     }
 
     /// Get block with highest priority (lowest id).
-    /// Return block if its id equals next out.
-    pub fn try_get_block(&mut self) -> Option<Block> {
+    /// Return block if its id equals next out, or the error of any 
+    /// failed job.
+    pub fn try_get_block(&mut self) -> Result<Option<Block>, ArchiveError> {
+        if let Some((_, err)) = self.failed.pop_first() {
+            return Err(err);
+        }
+        Ok(self.try_get_result().map(|(_, blk)| blk.unwrap()))
+    }
+
+    /// Return the id and result of the next block, if its job has
+    /// finished, so a failed block doesn't stop later blocks from being
+    /// returned.
+    pub fn try_get_result(&mut self) -> Option<(u32, Result<Block, ArchiveError>)> {
+        let id = self.next_out;
+        if let Some(err) = self.failed.remove(&id) {
+            self.next_out += 1;
+            return Some((id + self.offset, Err(err)));
+        }
         if let Some(blk) = self.blocks.peek() {
-            if blk.id == self.next_out {
+            if blk.id == id {
                 self.next_out += 1;
                 let mut block = self.blocks.pop().unwrap(); 
                 block.id += self.offset;
-                return Some(block);
+                return Some((block.id, Ok(block)));
             }
         }
         None
@@ -0,0 +1,291 @@
+use std::{
+    io::{self, Read, Write, Seek, SeekFrom, ErrorKind},
+    fs::{self, File, OpenOptions},
+    path::{Path, PathBuf},
+    cmp::min,
+};
+
+use crate::{
+    filedata::FileData,
+    error::ArchiveError,
+};
+
+// An archive can be split into volumes of a set size, named name.prsv.001,
+// name.prsv.002 and so on. Volumes are treated as one continuous archive,
+// so blocks can span volumes and the rest of the archive format is the
+// same as for a single file, which is treated as a set of one volume.
+
+/// Path of volume 'n' (starting at 1) of an archive.
+pub fn volume_path(base: &Path, n: usize) -> PathBuf {
+    let mut path = base.as_os_str().to_owned();
+    path.push(format!(".{n:03}"));
+    PathBuf::from(path)
+}
+
+/// Archive path without a volume number, so both name.prsv and
+/// name.prsv.001 refer to name.prsv.
+pub fn base_path(path: &Path) -> PathBuf {
+    let numbered = path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.len() == 3 && ext.bytes().all(|b| b.is_ascii_digit()));
+
+    let stem = path.with_extension("");
+    if numbered && stem.extension().is_some_and(|ext| ext == "prsv") {
+        stem
+    }
+    else {
+        path.to_path_buf()
+    }
+}
+
+/// Check if an archive exists, either as a single file or a volume set.
+pub fn exists(path: &Path) -> bool {
+    let base = base_path(path);
+    base.is_file() || volume_path(&base, 1).is_file()
+}
+
+/// Paths of the existing files of an archive, either the single file
+/// or every volume, and whether the archive is split.
+fn find(base: &Path) -> (Vec<PathBuf>, bool) {
+    if base.is_file() {
+        return (vec![base.to_path_buf()], false);
+    }
+    let paths = (1..)
+        .map(|n| volume_path(base, n))
+        .take_while(|path| path.is_file())
+        .collect::<Vec<PathBuf>>();
+    let split = !paths.is_empty();
+    (paths, split)
+}
+
+/// Size of the first volume of a volume set, or None if the archive
+/// isn't split.
+pub fn split_size(path: &Path) -> Option<u64> {
+    let (paths, split) = find(&base_path(path));
+    if !split {
+        return None;
+    }
+    paths.first()
+        .and_then(|path| path.metadata().ok())
+        .map(|meta| meta.len())
+}
+
+/// Path of a temporary archive to write in place of an archive.
+pub fn temp_path(path: &Path) -> PathBuf {
+    let mut path = base_path(path).into_os_string();
+    path.push(".tmp");
+    PathBuf::from(path)
+}
+
+/// Replace archive 'to' with archive 'from', where either can be a
+/// single file or volume set, removing any volumes of 'to' left over.
+pub fn replace(from: &Path, to: &Path) -> Result<(), ArchiveError> {
+    let to = base_path(to);
+    let (old, _) = find(&to);
+    let (new, split) = find(from);
+
+    let mut dests = Vec::with_capacity(new.len());
+    for (i, path) in new.iter().enumerate() {
+        let dest = if split { volume_path(&to, i + 1) } else { to.clone() };
+        fs::rename(path, &dest).map_err(|err| access_error(path, err))?;
+        dests.push(dest);
+    }
+    for path in old.iter().filter(|path| !dests.contains(path)) {
+        fs::remove_file(path).map_err(|err| access_error(path, err))?;
+    }
+    Ok(())
+}
+
+fn access_error(path: &Path, err: io::Error) -> ArchiveError {
+    match err.kind() {
+        ErrorKind::PermissionDenied => ArchiveError::FileAccessDenied(path.to_path_buf()),
+        _ => ArchiveError::IoError(err),
+    }
+}
+
+/// A single file or volume set archive, read and written as one file.
+pub struct Volumes {
+    base:   PathBuf,     // Archive path without volume number
+    limit:  Option<u64>, // Maximum volume size when writing
+    files:  Vec<File>,   // Open volumes
+    lens:   Vec<u64>,    // Volume lengths
+    pos:    u64,         // Position in archive
+}
+impl Volumes {
+    /// Open an existing archive for reading.
+    pub fn open(path: &Path) -> Result<Volumes, ArchiveError> {
+        let base = base_path(path);
+        let (paths, _) = find(&base);
+        if paths.is_empty() {
+            return Err(ArchiveError::IoError(io::Error::from(ErrorKind::NotFound)));
+        }
+        let mut volumes = Volumes {
+            base, limit: None, files: Vec::new(), lens: Vec::new(), pos: 0,
+        };
+        for path in paths.iter() {
+            let file = File::open(path).map_err(|err| access_error(path, err))?;
+            volumes.lens.push(file.metadata()?.len());
+            volumes.files.push(file);
+        }
+        Ok(volumes)
+    }
+
+    /// Open a new or existing archive for writing. A new archive is split
+    /// into volumes of at most 'size' bytes if a size is given, and an
+    /// existing volume set keeps its volume size unless one is given.
+    pub fn create(arch: &FileData, size: Option<u64>, clobber: bool) -> Result<Volumes, ArchiveError> {
+        let base = base_path(&arch.path);
+        let (mut paths, mut split) = find(&base);
+
+        let len = paths.iter()
+            .filter_map(|path| path.metadata().ok())
+            .map(|meta| meta.len())
+            .sum::<u64>();
+        if len > 0 && !clobber {
+            return Err(ArchiveError::FileAlreadyExists(base));
+        }
+
+        // Replace existing archive
+        if arch.seg_beg == 0 {
+            for path in paths.drain(..) {
+                fs::remove_file(&path).map_err(|err| access_error(&path, err))?;
+            }
+            split = size.is_some();
+        }
+
+        let mut volumes = Volumes {
+            base, limit: None, files: Vec::new(), lens: Vec::new(), pos: 0,
+        };
+        if !split {
+            paths = vec![volumes.base.clone()];
+        }
+        for path in paths.iter() {
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(path)
+                .map_err(|err| access_error(path, err))?;
+            volumes.lens.push(file.metadata()?.len());
+            volumes.files.push(file);
+        }
+        if split {
+            volumes.limit = match volumes.lens.len() {
+                0 | 1 => size,
+                _ => size.or(Some(volumes.lens[0])),
+            };
+        }
+        Ok(volumes)
+    }
+
+    /// Volume containing position 'pos' and the offset into it. Positions
+    /// at or past the end are in the last volume.
+    fn locate(&self, pos: u64) -> (usize, u64) {
+        let mut beg = 0;
+        for (i, len) in self.lens.iter().enumerate() {
+            if pos < beg + len {
+                return (i, pos - beg);
+            }
+            beg += len;
+        }
+        match self.lens.last() {
+            Some(len) => (self.lens.len() - 1, pos - (beg - len)),
+            None => (0, pos),
+        }
+    }
+
+    /// Add a new volume to the end of a volume set.
+    fn add_volume(&mut self) -> io::Result<()> {
+        let path = volume_path(&self.base, self.files.len() + 1);
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        self.files.push(file);
+        self.lens.push(0);
+        Ok(())
+    }
+
+    fn len(&self) -> u64 {
+        self.lens.iter().sum()
+    }
+
+    /// Truncate archive to 'len' bytes, removing any volumes past the end.
+    pub fn set_len(&mut self, len: u64) -> io::Result<()> {
+        let (i, off) = self.locate(len);
+        if i < self.files.len() {
+            self.files[i].set_len(off)?;
+            self.lens[i] = off;
+        }
+        let keep = if off == 0 && i > 0 { i } else { i + 1 };
+        while self.files.len() > keep {
+            self.files.pop();
+            self.lens.pop();
+            fs::remove_file(volume_path(&self.base, self.files.len() + 1))?;
+        }
+        Ok(())
+    }
+}
+impl Read for Volumes {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let (i, off) = self.locate(self.pos);
+        if i >= self.files.len() || off >= self.lens[i] {
+            return Ok(0);
+        }
+        let len = min(buf.len() as u64, self.lens[i] - off) as usize;
+        self.files[i].seek(SeekFrom::Start(off))?;
+        let n = self.files[i].read(&mut buf[..len])?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+impl Write for Volumes {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let (mut i, mut off) = self.locate(self.pos);
+
+        // Roll over to next volume when the current one is full, first
+        // dropping the rest of a volume written with a larger size
+        if let Some(limit) = self.limit {
+            if off >= limit {
+                if self.lens.get(i).is_some_and(|len| *len > off) {
+                    self.files[i].set_len(off)?;
+                    self.lens[i] = off;
+                }
+                i += 1;
+                off = 0;
+            }
+        }
+        while i >= self.files.len() {
+            self.add_volume()?;
+        }
+        let len = match self.limit {
+            Some(limit) => min(buf.len() as u64, limit - off) as usize,
+            None => buf.len(),
+        };
+        self.files[i].seek(SeekFrom::Start(off))?;
+        let n = self.files[i].write(&buf[..len])?;
+        self.lens[i] = self.lens[i].max(off + n as u64);
+        self.pos += n as u64;
+        Ok(n)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        for file in self.files.iter_mut() {
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+impl Seek for Volumes {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos)   => Some(pos),
+            SeekFrom::End(off)     => self.len().checked_add_signed(off),
+            SeekFrom::Current(off) => self.pos.checked_add_signed(off),
+        };
+        self.pos = pos.ok_or_else(|| io::Error::from(ErrorKind::InvalidInput))?;
+        Ok(self.pos)
+    }
+}
@@ -2,9 +2,1353 @@
 #[cfg(test)]
 mod tests {
     use prisirv::Prisirv;
-    use prisirv::error::PrisirvError;
+    use prisirv::error::{PrisirvError, ArchiveError};
     use prisirv::crc32::Crc32;
-    use std::{fs, path::Path};
+    use prisirv::config::{Method, Growth, Filter};
+    use prisirv::filedata::{FileData, Type};
+    use std::{fs, path::{Path, PathBuf}};
+
+    const FILES: [&str; 3] = ["text.txt", "data.bin", "prog.exe"];
+
+    /// Create an empty directory 'name' in the system temp directory
+    /// containing an input directory with a text file, a binary file and
+    /// an executable-like file full of x86 calls.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(name);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(dir.join("in")).unwrap();
+
+        let words = ["the", "quick", "brown", "fox", "jumps", "over", "lazy", "dog"];
+        let mut seed = 1u32;
+        let mut text = String::new();
+        let mut bin = Vec::new();
+        let mut exe = pe_header();
+        for _ in 0..5000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            text.push_str(words[(seed >> 16) as usize % words.len()]);
+            text.push(if (seed >> 8) & 15 == 0 { '\n' } else { ' ' });
+            bin.push((seed >> 24) as u8);
+
+            let target = [0x1000, 0x2400, 0x8000][(seed >> 16) as usize % 3];
+            exe.push(0xE8);
+            exe.extend_from_slice(&(target - exe.len() as i32 - 4).to_le_bytes());
+            exe.extend_from_slice(&seed.to_le_bytes()[..3]);
+        }
+        fs::write(dir.join("in/text.txt"), text).unwrap();
+        fs::write(dir.join("in/data.bin"), bin).unwrap();
+        fs::write(dir.join("in/prog.exe"), exe).unwrap();
+        dir
+    }
+
+    /// Minimal DOS stub pointing to a PE signature.
+    fn pe_header() -> Vec<u8> {
+        let mut head = vec![0u8; 0x44];
+        head[..2].copy_from_slice(b"MZ");
+        head[0x3C] = 0x40;
+        head[0x40..].copy_from_slice(b"PE\0\0");
+        head
+    }
+
+    /// Archive the inputs of a test directory with the given archiver, 
+    /// extract the archive, and check that the extracted files match.
+    fn roundtrip(name: &str, archiver: Prisirv) -> Result<(), PrisirvError> {
+        let dir = test_dir(name);
+        let input = dir.join("in");
+
+        archiver
+        .quiet()
+        .clobber()
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("out")
+        .arch(dir.join("in.prsv").to_str().unwrap())?
+        .extract_archive()?;
+
+        for file in FILES {
+            assert_eq!(
+                fs::read(input.join(file)).unwrap(), 
+                fs::read(dir.join("out/in").join(file)).unwrap()
+            );
+        }
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn lzw() -> Result<(), PrisirvError> {
+        roundtrip("prisirv_lzw", Prisirv::default().method(Method::Lzw))
+    }
+
+    #[test]
+    fn lzw_entropy_coded() -> Result<(), PrisirvError> {
+        roundtrip("prisirv_lzw_ec", Prisirv::default().method(Method::LzwEc))
+    }
+
+    #[test]
+    fn lzmw() -> Result<(), PrisirvError> {
+        roundtrip("prisirv_lzmw", Prisirv::default().growth(Growth::Lzmw))
+    }
+
+    #[test]
+    fn lzap_entropy_coded() -> Result<(), PrisirvError> {
+        roundtrip("prisirv_lzap_ec", Prisirv::default().method(Method::LzwEc).growth(Growth::Lzap))
+    }
+
+    #[test]
+    fn context_mixing() -> Result<(), PrisirvError> {
+        roundtrip("prisirv_cm", Prisirv::default().method(Method::Cm))
+    }
+
+    #[test]
+    fn lz77() -> Result<(), PrisirvError> {
+        roundtrip("prisirv_lz77", Prisirv::default().method(Method::Lz77))
+    }
+
+    #[test]
+    fn bwt() -> Result<(), PrisirvError> {
+        roundtrip("prisirv_bwt", Prisirv::default().method(Method::Bwt))
+    }
+
+    #[test]
+    fn x86_filter() -> Result<(), PrisirvError> {
+        roundtrip("prisirv_x86", Prisirv::default().filter(Filter::X86))
+    }
+
+    #[test]
+    fn delta_filter() -> Result<(), PrisirvError> {
+        roundtrip("prisirv_delta", Prisirv::default().filter(Filter::Delta(0)))
+    }
+
+    #[test]
+    fn filter_ext() -> Result<(), PrisirvError> {
+        roundtrip("prisirv_filter_ext", 
+            Prisirv::default()
+            .filter_ext("bin", Filter::Delta(3))
+            .filter_ext("txt", Filter::X86)
+            .block_size(4096)
+        )
+    }
+
+    #[test]
+    fn words_filter() -> Result<(), PrisirvError> {
+        roundtrip("prisirv_words", Prisirv::default().words().block_size(8192))
+    }
+
+    #[test]
+    fn auto() -> Result<(), PrisirvError> {
+        roundtrip("prisirv_auto", Prisirv::default().auto(10)?.block_size(4096))
+    }
+
+    #[test]
+    fn auto_stores_incompressible() -> Result<(), PrisirvError> {
+        let dir = test_dir("prisirv_auto_store");
+        let input = dir.join("in/data.bin");
+        let len = fs::metadata(&input).unwrap().len();
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .auto(0)?
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        // Data block stored as is, plus two headers
+        let arch_len = fs::metadata(dir.join("in/data.prsv")).unwrap().len();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(arch_len < len + 256);
+        Ok(())
+    }
+
+    /// Find all files under a directory.
+    fn walk(dir: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                files.extend(walk(&path));
+            }
+            else {
+                files.push(path);
+            }
+        }
+        files
+    }
+
+    #[test]
+    fn index() -> Result<(), PrisirvError> {
+        let dir = test_dir("prisirv_index");
+        let input = dir.join("in");
+        let arch = dir.join("in.prsv");
+        let has_index = || fs::read(&arch).unwrap().ends_with(b"PRSI");
+        fs::write(dir.join("extra.txt"), "an extra file").unwrap();
+        fs::write(dir.join("more.txt"), "one more file").unwrap();
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .index()
+        .block_size(4096)
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+        assert!(has_index());
+
+        Prisirv::default()
+        .quiet()
+        .index()
+        .arch(arch.to_str().unwrap())?
+        .inputs(&[dir.join("extra.txt").to_str().unwrap()])?
+        .append_files()?;
+        assert!(has_index());
+
+        // Appending without an index drops the old one
+        Prisirv::default()
+        .quiet()
+        .arch(arch.to_str().unwrap())?
+        .inputs(&[dir.join("more.txt").to_str().unwrap()])?
+        .append_files()?;
+        assert!(!has_index());
+
+        let info = Prisirv::default()
+        .quiet()
+        .arch(arch.to_str().unwrap())?
+        .info()?
+        .to_string();
+        assert!(info.contains("extra.txt") && info.contains("more.txt"));
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("pick")
+        .arch(arch.to_str().unwrap())?
+        .inputs(&[input.join("text.txt").to_str().unwrap()])?
+        .extract_files()?;
+
+        let picked = walk(&dir.join("pick"));
+        assert!(picked.len() == 1 && picked[0].ends_with("text.txt"));
+        assert_eq!(fs::read(&picked[0]).unwrap(), fs::read(input.join("text.txt")).unwrap());
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("out")
+        .arch(arch.to_str().unwrap())?
+        .extract_archive()?;
+
+        let extracted = walk(&dir.join("out"));
+        for file in FILES.iter().chain(["extra.txt", "more.txt"].iter()) {
+            assert!(extracted.iter().any(|path| path.ends_with(file)), "{}", file);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn file_checksum() -> Result<(), PrisirvError> {
+        let dir = test_dir("prisirv_file_checksum");
+        let input = dir.join("in/data.bin");
+        let arch = dir.join("in/data.prsv");
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .method(Method::Store)
+        .block_size(4096)
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        // Corrupt the file checksum stored with the last segment, which
+        // stored blocks can't detect on their own.
+        let mut data = fs::read(&arch).unwrap();
+        let mut path = input.to_str().unwrap().as_bytes().to_vec();
+        path.push(0);
+        let pos = data.windows(path.len()).rposition(|w| w == path.as_slice()).unwrap();
+        data[pos + path.len() + 32] ^= 1;
+        fs::write(&arch, data).unwrap();
+
+        let result = Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("out")
+        .arch(arch.to_str().unwrap())?
+        .extract_archive();
+        fs::remove_dir_all(&dir).unwrap();
+
+        match result {
+            Err(ArchiveError::IncorrectFileChecksum(path)) => assert_eq!(path, input),
+            _ => panic!("corrupted file checksum not detected"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn oversized_mem() -> Result<(), PrisirvError> {
+        let dir = test_dir("prisirv_oversized_mem");
+        let input = dir.join("in/text.txt");
+        let arch = dir.join("in/text.prsv");
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .method(Method::Cm)
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        // Claim far more memory than any '-mem' option, after the magic 
+        // number and version of the first block.
+        let mut data = fs::read(&arch).unwrap();
+        data[10..18].copy_from_slice(&u64::MAX.to_le_bytes());
+        fs::write(&arch, data).unwrap();
+
+        let result = Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("out")
+        .arch(arch.to_str().unwrap())?
+        .extract_archive();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(result, Err(ArchiveError::InvalidHeader(_))));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn metadata() -> Result<(), PrisirvError> {
+        use std::{fs::FileTimes, os::unix::fs::PermissionsExt, time::{Duration, SystemTime}};
+
+        let dir = test_dir("prisirv_metadata");
+        let input = dir.join("in");
+        let mtime = SystemTime::UNIX_EPOCH + Duration::new(1_000_000_000, 123_456_789);
+
+        let exe = fs::File::options().write(true).open(input.join("prog.exe")).unwrap();
+        exe.set_times(FileTimes::new().set_modified(mtime)).unwrap();
+        fs::set_permissions(input.join("prog.exe"), fs::Permissions::from_mode(0o750)).unwrap();
+
+        // Directories that aren't empty keep their metadata too
+        fs::File::open(&input).unwrap().set_times(FileTimes::new().set_modified(mtime)).unwrap();
+        fs::set_permissions(&input, fs::Permissions::from_mode(0o750)).unwrap();
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("out")
+        .arch(dir.join("in.prsv").to_str().unwrap())?
+        .extract_archive()?;
+
+        for path in ["out/in/prog.exe", "out/in"] {
+            let meta = fs::metadata(dir.join(path)).unwrap();
+            assert_eq!(meta.permissions().mode() & 0o7777, 0o750);
+            assert_eq!(meta.modified().unwrap(), mtime);
+        }
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .no_perms()
+        .no_times()
+        .output("plain")
+        .arch(dir.join("in.prsv").to_str().unwrap())?
+        .extract_archive()?;
+
+        let meta = fs::metadata(dir.join("plain/in/prog.exe")).unwrap();
+        assert_ne!(meta.permissions().mode() & 0o7777, 0o750);
+        assert_ne!(meta.modified().unwrap(), mtime);
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links() -> Result<(), PrisirvError> {
+        use std::os::unix::fs::{symlink, MetadataExt};
+
+        let dir = test_dir("prisirv_links");
+        let input = dir.join("in");
+        fs::create_dir(input.join("empty")).unwrap();
+        symlink("text.txt", input.join("link.txt")).unwrap();
+        fs::hard_link(input.join("data.bin"), input.join("hard.bin")).unwrap();
+
+        // A directory containing itself, which must not loop when following links
+        symlink(".", input.join("self")).unwrap();
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("out")
+        .arch(dir.join("in.prsv").to_str().unwrap())?
+        .extract_archive()?;
+
+        let out = dir.join("out/in");
+        assert!(out.join("empty").is_dir());
+        assert_eq!(fs::read_link(out.join("link.txt")).unwrap(), Path::new("text.txt"));
+        assert_eq!(fs::read_link(out.join("self")).unwrap(), Path::new("."));
+        assert_eq!(
+            fs::metadata(out.join("hard.bin")).unwrap().ino(), 
+            fs::metadata(out.join("data.bin")).unwrap().ino()
+        );
+        assert_eq!(fs::read(out.join("hard.bin")).unwrap(), fs::read(input.join("data.bin")).unwrap());
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .follow_links()
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("followed")
+        .arch(dir.join("in.prsv").to_str().unwrap())?
+        .extract_archive()?;
+
+        let out = dir.join("followed/in");
+        assert!(!out.join("link.txt").is_symlink());
+        assert_eq!(fs::read(out.join("link.txt")).unwrap(), fs::read(input.join("text.txt")).unwrap());
+        assert_ne!(
+            fs::metadata(out.join("hard.bin")).unwrap().ino(), 
+            fs::metadata(out.join("data.bin")).unwrap().ino()
+        );
+        assert!(!out.join("self").exists());
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unsafe_links() -> Result<(), PrisirvError> {
+        use std::os::unix::fs::symlink;
+
+        let dir = test_dir("prisirv_unsafe_links");
+        let input = dir.join("in");
+        let arch = dir.join("in.prsv");
+        let extract = |out: &str| {
+            Prisirv::default()
+            .quiet()
+            .clobber()
+            .output(out)
+            .arch(arch.to_str().unwrap()).unwrap()
+            .extract_archive()
+        };
+
+        // A link pointing outside the output directory
+        symlink("../../outside", input.join("escape")).unwrap();
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        assert!(matches!(extract("out"), Err(ArchiveError::UnsafePath(_))));
+        assert!(!dir.join("out/in/escape").is_symlink());
+
+        // A link inside the output directory, followed by a file under the
+        // link's path, which must not be written through the link
+        fs::remove_file(input.join("escape")).unwrap();
+        symlink("../other", input.join("sub")).unwrap();
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        fs::remove_file(input.join("sub")).unwrap();
+        fs::create_dir(input.join("sub")).unwrap();
+        fs::write(input.join("sub/file.txt"), "through a link").unwrap();
+        Prisirv::default()
+        .quiet()
+        .arch(arch.to_str().unwrap())?
+        .inputs(&[input.join("sub/file.txt").to_str().unwrap()])?
+        .append_files()?;
+
+        assert!(matches!(extract("out2"), Err(ArchiveError::UnsafePath(_))));
+        assert!(!dir.join("out2/other/file.txt").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn stdio() {
+        use std::process::{Command, Stdio};
+
+        let dir = test_dir("prisirv_stdio");
+        let input = dir.join("in");
+        let prisirv = env!("CARGO_BIN_EXE_prisirv");
+        fs::create_dir(dir.join("out")).unwrap();
+
+        let mut create = Command::new(prisirv)
+            .args(["create", "-inputs", input.to_str().unwrap(), "-output-path", "-"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        let status = Command::new(prisirv)
+            .args(["extract", "-", "-quiet"])
+            .current_dir(dir.join("out"))
+            .stdin(create.stdout.take().unwrap())
+            .status()
+            .unwrap();
+        assert!(status.success() && create.wait().unwrap().success());
+
+        let extracted = walk(&dir.join("out"));
+        for file in FILES {
+            let path = extracted.iter().find(|path| path.ends_with(file)).unwrap();
+            assert_eq!(fs::read(input.join(file)).unwrap(), fs::read(path).unwrap());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn in_memory() -> Result<(), PrisirvError> {
+        use std::io::Cursor;
+
+        let dir = test_dir("prisirv_in_memory");
+        let input = dir.join("in");
+
+        let arch = Prisirv::default()
+        .quiet()
+        .index()
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive_to(Vec::new())?;
+
+        let info = Prisirv::default()
+        .quiet()
+        .info_from(Cursor::new(&arch))?
+        .to_string();
+        assert!(FILES.iter().all(|file| info.contains(file)));
+
+        Prisirv::default()
+        .quiet()
+        .output(dir.join("out").to_str().unwrap())
+        .extract_archive_from(arch.as_slice())?;
+
+        Prisirv::default()
+        .quiet()
+        .output(dir.join("pick").to_str().unwrap())
+        .inputs(&[input.join("data.bin").to_str().unwrap()])?
+        .extract_files_from(Cursor::new(&arch))?;
+
+        let extracted = walk(&dir.join("out"));
+        for file in FILES {
+            let path = extracted.iter().find(|path| path.ends_with(file)).unwrap();
+            assert_eq!(fs::read(input.join(file)).unwrap(), fs::read(path).unwrap());
+        }
+        let picked = walk(&dir.join("pick"));
+        assert!(picked.len() == 1 && picked[0].ends_with("data.bin"));
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn encryption() -> Result<(), PrisirvError> {
+        use std::io::Cursor;
+
+        let dir = test_dir("prisirv_encryption");
+        let input = dir.join("in");
+
+        let arch = Prisirv::default()
+        .quiet()
+        .method(Method::Store)
+        .password("hunter2")
+        .index()
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive_to(Vec::new())?;
+
+        // Neither file names nor file contents should be visible
+        let text = fs::read(input.join("text.txt")).unwrap();
+        for needle in [b"text.txt".as_slice(), &text[..64]] {
+            assert!(!arch.windows(needle.len()).any(|w| w == needle));
+        }
+
+        Prisirv::default()
+        .quiet()
+        .password("hunter2")
+        .output(dir.join("out").to_str().unwrap())
+        .extract_archive_from(arch.as_slice())?;
+
+        Prisirv::default()
+        .quiet()
+        .password("hunter2")
+        .output(dir.join("pick").to_str().unwrap())
+        .inputs(&[input.join("data.bin").to_str().unwrap()])?
+        .extract_files_from(Cursor::new(&arch))?;
+
+        let extracted = walk(&dir.join("out"));
+        for file in FILES {
+            let path = extracted.iter().find(|path| path.ends_with(file)).unwrap();
+            assert_eq!(fs::read(input.join(file)).unwrap(), fs::read(path).unwrap());
+        }
+        let picked = walk(&dir.join("pick"));
+        assert!(picked.len() == 1 && picked[0].ends_with("data.bin"));
+
+        // Password read from a file, or from the environment by the binary
+        fs::write(dir.join("password.txt"), "hunter2\n").unwrap();
+        Prisirv::default()
+        .quiet()
+        .password_file(dir.join("password.txt").to_str().unwrap())?
+        .output(dir.join("file").to_str().unwrap())
+        .extract_archive_from(arch.as_slice())?;
+        assert_eq!(walk(&dir.join("file")).len(), FILES.len());
+
+        fs::write(dir.join("enc.prsv"), &arch).unwrap();
+        let status = std::process::Command::new(env!("CARGO_BIN_EXE_prisirv"))
+            .args(["extract", dir.join("enc.prsv").to_str().unwrap(), "-quiet", "-output-path", "env"])
+            .env("PRISIRV_PASSWORD", "hunter2")
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success());
+        assert_eq!(walk(&dir.join("env")).len(), FILES.len());
+
+        let result = Prisirv::default()
+        .quiet()
+        .output(dir.join("none").to_str().unwrap())
+        .extract_archive_from(arch.as_slice());
+        assert!(matches!(result, Err(ArchiveError::PasswordRequired)));
+
+        let result = Prisirv::default()
+        .quiet()
+        .password("hunter3")
+        .output(dir.join("wrong").to_str().unwrap())
+        .extract_archive_from(arch.as_slice());
+        assert!(matches!(result, Err(ArchiveError::DecryptionFailed(_))));
+
+        // Modifying any byte of an encrypted block is detected
+        let mut tampered = arch.clone();
+        let len = tampered.len();
+        tampered[len / 2] ^= 1;
+        let result = Prisirv::default()
+        .quiet()
+        .password("hunter2")
+        .output(dir.join("tampered").to_str().unwrap())
+        .extract_archive_from(tampered.as_slice());
+        assert!(matches!(result, Err(ArchiveError::DecryptionFailed(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn recipients() -> Result<(), PrisirvError> {
+        use std::io::Cursor;
+
+        let dir = test_dir("prisirv_recipients");
+        let input = dir.join("in");
+        let key = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+        for name in ["alice", "bob", "eve"] {
+            Prisirv::default().output(&key(name)).generate_keys()?;
+        }
+
+        let arch = Prisirv::default()
+        .quiet()
+        .recipient(&key("alice.pub"))?
+        .recipient(&key("bob.pub"))?
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive_to(Vec::new())?;
+
+        // Either recipient can list and extract the archive
+        for name in ["alice.key", "bob.key"] {
+            let info = Prisirv::default()
+            .quiet()
+            .identity(&key(name))?
+            .info_from(Cursor::new(&arch))?
+            .to_string();
+            assert!(FILES.iter().all(|file| info.contains(file)));
+
+            let out = dir.join(name.replace(".key", ""));
+            Prisirv::default()
+            .quiet()
+            .identity(&key(name))?
+            .output(out.to_str().unwrap())
+            .extract_archive_from(arch.as_slice())?;
+
+            let extracted = walk(&out);
+            for file in FILES {
+                let path = extracted.iter().find(|path| path.ends_with(file)).unwrap();
+                assert_eq!(fs::read(input.join(file)).unwrap(), fs::read(path).unwrap());
+            }
+        }
+
+        let info = Prisirv::default()
+        .quiet()
+        .info_from(Cursor::new(&arch))?
+        .to_string();
+        assert!(!FILES.iter().any(|file| info.contains(file)));
+
+        let result = Prisirv::default()
+        .quiet()
+        .output(dir.join("none").to_str().unwrap())
+        .extract_archive_from(arch.as_slice());
+        assert!(matches!(result, Err(ArchiveError::IdentityRequired)));
+
+        let result = Prisirv::default()
+        .quiet()
+        .identity(&key("eve.key"))?
+        .output(dir.join("eve").to_str().unwrap())
+        .extract_archive_from(arch.as_slice());
+        assert!(matches!(result, Err(ArchiveError::NoMatchingIdentity)));
+
+        // A recipient key can't be used as an identity
+        let result = Prisirv::default()
+        .quiet()
+        .identity(&key("alice.pub"))?
+        .output(dir.join("pub").to_str().unwrap())
+        .extract_archive_from(arch.as_slice());
+        assert!(matches!(result, Err(ArchiveError::NoMatchingIdentity)));
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn signatures() -> Result<(), PrisirvError> {
+        let dir = test_dir("prisirv_signatures");
+        let input = dir.join("in");
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+
+        for name in ["release", "other"] {
+            Prisirv::default().signing().output(&path(name)).generate_keys()?;
+        }
+        let verify = |arch: &str, signer: &str| {
+            Prisirv::default()
+            .signer(&path(signer)).unwrap()
+            .arch(&path(arch)).unwrap()
+            .verify_signature()
+        };
+
+        // Embedded signature, followed by an index
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .index()
+        .sign(&path("release.key"))?
+        .inputs(&[input.join("text.txt").to_str().unwrap()])?
+        .create_archive()?;
+        assert!(verify("in/text.prsv", "release.pub").is_ok());
+        assert!(matches!(verify("in/text.prsv", "other.pub"), Err(ArchiveError::UntrustedSigner(_))));
+
+        // Appending re-signs the whole archive
+        Prisirv::default()
+        .quiet()
+        .sign(&path("release.key"))?
+        .arch(&path("in/text.prsv"))?
+        .inputs(&[input.join("data.bin").to_str().unwrap()])?
+        .append_files()?;
+        assert!(verify("in/text.prsv", "release.pub").is_ok());
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .signer(&path("release.pub"))?
+        .output("out")
+        .arch(&path("in/text.prsv"))?
+        .extract_archive()?;
+        let extracted = walk(&input.join("out"));
+        assert_eq!(extracted.len(), 2);
+
+        let mut data = fs::read(path("in/text.prsv")).unwrap();
+        let len = data.len();
+        data[len / 2] ^= 1;
+        fs::write(path("in/text.prsv"), data).unwrap();
+        assert!(matches!(verify("in/text.prsv", "release.pub"), Err(ArchiveError::InvalidSignature)));
+
+        let result = Prisirv::default()
+        .quiet()
+        .clobber()
+        .signer(&path("release.pub"))?
+        .output("tampered")
+        .arch(&path("in/text.prsv"))?
+        .extract_archive();
+        assert!(matches!(result, Err(ArchiveError::InvalidSignature)));
+        assert!(!input.join("tampered").exists());
+
+        let status = std::process::Command::new(env!("CARGO_BIN_EXE_prisirv"))
+            .args(["extract", &path("in/text.prsv"), "-signer", &path("release.pub"), "-quiet"])
+            .args(["-output-path", "tampered"])
+            .output()
+            .unwrap()
+            .status;
+        assert!(!status.success());
+        assert!(!input.join("tampered").exists());
+
+        // Detached signature
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .detached()
+        .sign(&path("other.key"))?
+        .output("detached")
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+        assert!(verify("detached.prsv", "other.pub").is_ok());
+
+        fs::remove_file(path("detached.prsv.sig")).unwrap();
+        assert!(matches!(verify("detached.prsv", "other.pub"), Err(ArchiveError::SignatureMissing)));
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn forged_index() -> Result<(), PrisirvError> {
+        use std::convert::TryInto;
+
+        let dir = test_dir("prisirv_forged_index");
+        let input = dir.join("in");
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        Prisirv::default().signing().output(&path("release")).generate_keys()?;
+
+        let create = |name: &str, file: &str, sign: bool| -> Result<Vec<u8>, PrisirvError> {
+            let mut prisirv = Prisirv::default()
+            .quiet()
+            .clobber()
+            .index()
+            .output(name)
+            .inputs(&[input.join(file).to_str().unwrap()])?;
+            if sign {
+                prisirv = prisirv.sign(&path("release.key"))?;
+            }
+            prisirv.create_archive()?;
+            Ok(fs::read(input.join(format!("{name}.prsv"))).unwrap())
+        };
+        let signed = create("signed", "text.txt", true)?;
+        let other = create("other", "prog.exe", false)?;
+
+        // Position of an archive's index and the range of its block entries
+        let index = |data: &[u8]| {
+            let len = data.len() - 12;
+            let pos = u64::from_le_bytes(data[len..len + 8].try_into().unwrap()) as usize;
+            (pos, pos + 12..len)
+        };
+        let (pos, entries) = index(&signed);
+        let (other_pos, other_entries) = index(&other);
+        let count = u32::from_le_bytes(signed[pos + 8..pos + 12].try_into().unwrap());
+
+        // Keep the signed blocks and trailer, then append a block of the 
+        // other archive and an index listing it, with the original end of data
+        let mut forged = signed[..pos].to_vec();
+        let eod = u64::from_le_bytes(other[other_pos..other_pos + 8].try_into().unwrap());
+        forged.extend_from_slice(&other[..eod as usize]);
+
+        let index_pos = forged.len() as u64;
+        forged.extend_from_slice(&signed[pos..pos + 8]);
+        forged.extend_from_slice(&(count + 1).to_le_bytes());
+        forged.extend_from_slice(&signed[entries]);
+        forged.extend_from_slice(&(pos as u64).to_le_bytes());
+        forged.extend_from_slice(&other[other_entries.start + 8..other_entries.end]);
+        forged.extend_from_slice(&index_pos.to_le_bytes());
+        forged.extend_from_slice(&signed[signed.len() - 4..]);
+        fs::write(input.join("signed.prsv"), forged).unwrap();
+
+        for file in ["text.txt", "prog.exe"] {
+            Prisirv::default()
+            .quiet()
+            .clobber()
+            .signer(&path("release.pub"))?
+            .output("pick")
+            .arch(&path("in/signed.prsv"))?
+            .inputs(&[input.join(file).to_str().unwrap()])?
+            .extract_files()?;
+        }
+        let picked = walk(&input.join("pick"));
+        assert!(picked.len() == 1 && picked[0].ends_with("text.txt"));
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn volumes() -> Result<(), PrisirvError> {
+        let dir = test_dir("prisirv_volumes");
+        let input = dir.join("in");
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .method(Method::Store)
+        .volume_size(16 * 1024)
+        .index()
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        let volumes = fs::read_dir(&dir).unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_str().unwrap().contains("in.prsv."))
+            .collect::<Vec<PathBuf>>();
+        assert!(volumes.len() > 2);
+        assert!(!dir.join("in.prsv").exists());
+        for n in 1..volumes.len() {
+            let path = dir.join(format!("in.prsv.{n:03}"));
+            assert_eq!(fs::metadata(path).unwrap().len(), 16 * 1024);
+        }
+
+        let info = Prisirv::default()
+        .quiet()
+        .arch(dir.join("in.prsv").to_str().unwrap())?
+        .info()?
+        .to_string();
+        assert!(FILES.iter().all(|file| info.contains(file)));
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("out")
+        .arch(dir.join("in.prsv.001").to_str().unwrap())?
+        .extract_archive()?;
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("pick")
+        .inputs(&[input.join("prog.exe").to_str().unwrap()])?
+        .arch(dir.join("in.prsv").to_str().unwrap())?
+        .extract_files()?;
+
+        for file in FILES {
+            assert_eq!(
+                fs::read(input.join(file)).unwrap(), 
+                fs::read(dir.join("out/in").join(file)).unwrap()
+            );
+        }
+        let picked = walk(&dir.join("pick"));
+        assert!(picked.len() == 1 && picked[0].ends_with("prog.exe"));
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn volumes_append() -> Result<(), PrisirvError> {
+        let dir = test_dir("prisirv_volumes_append");
+        let input = dir.join("in");
+        fs::write(dir.join("extra.txt"), "appended to smaller volumes").unwrap();
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .method(Method::Store)
+        .volume_size(64 * 1024)
+        .inputs(&[input.join("text.txt").to_str().unwrap()])?
+        .create_archive()?;
+
+        // Volumes past the end of the old data are smaller than the first
+        Prisirv::default()
+        .quiet()
+        .method(Method::Store)
+        .volume_size(4 * 1024)
+        .arch(input.join("text.prsv").to_str().unwrap())?
+        .inputs(&[input.join("data.bin").to_str().unwrap(), dir.join("extra.txt").to_str().unwrap()])?
+        .append_files()?;
+
+        let report = Prisirv::default()
+        .quiet()
+        .arch(input.join("text.prsv").to_str().unwrap())?
+        .test_archive()?;
+        assert!(report.passed());
+        assert_eq!(report.files.len(), 3);
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("out")
+        .arch(input.join("text.prsv").to_str().unwrap())?
+        .extract_archive()?;
+
+        let files = walk(&input.join("out"));
+        assert_eq!(files.len(), 3);
+        for file in files {
+            let name = file.file_name().unwrap();
+            let orig = if name == "extra.txt" { dir.join(name) } else { input.join(name) };
+            assert_eq!(fs::read(&file).unwrap(), fs::read(orig).unwrap());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn recovery() -> Result<(), PrisirvError> {
+        use std::convert::TryInto;
+
+        let dir = test_dir("prisirv_recovery");
+        let input = dir.join("in");
+        let arch = dir.join("in.prsv");
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .index()
+        .recovery(10)?
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        let repair = || {
+            Prisirv::default()
+            .quiet()
+            .arch(arch.to_str().unwrap()).unwrap()
+            .repair()
+        };
+        assert!(repair()?.is_empty());
+
+        // Damage a block header and a run of block data
+        let original = fs::read(&arch).unwrap();
+        let mut data = original.clone();
+        data[..16].fill(0);
+        for byte in data[4000..6000].iter_mut() {
+            *byte ^= 0x55;
+        }
+        fs::write(&arch, data).unwrap();
+
+        let rebuilt = repair()?;
+        assert!(rebuilt.iter().any(|range| range.start == 0));
+        assert!(rebuilt.iter().any(|range| range.contains(&5000)));
+        assert_eq!(fs::read(&arch).unwrap(), original);
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("out")
+        .arch(arch.to_str().unwrap())?
+        .extract_archive()?;
+        for file in FILES {
+            assert_eq!(
+                fs::read(input.join(file)).unwrap(), 
+                fs::read(dir.join("out/in").join(file)).unwrap()
+            );
+        }
+
+        // A damaged record header and footer are rebuilt from the copy of
+        // the header
+        let mut data = original.clone();
+        let len = data.len();
+        let pos = u64::from_le_bytes(data[len - 12..len - 4].try_into().unwrap()) as usize;
+        data[pos + 20] ^= 1;
+        data[len - 12..].fill(0);
+        data[100] ^= 1;
+        fs::write(&arch, data).unwrap();
+        assert!(repair()?.iter().any(|range| range.contains(&100)));
+        assert_eq!(fs::read(&arch).unwrap(), original);
+
+        // Damage beyond the recovery record can't be repaired
+        let mut data = original.clone();
+        let len = data.len();
+        data[..len / 2].fill(0);
+        fs::write(&arch, data).unwrap();
+        assert!(matches!(repair(), Err(ArchiveError::RepairFailed(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive() -> Result<(), PrisirvError> {
+        let dir = test_dir("prisirv_test_archive");
+        let input = dir.join("in");
+        let arch = dir.join("in.prsv");
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .method(Method::Store)
+        .block_size(4096)
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        let test = || {
+            Prisirv::default()
+            .quiet()
+            .arch(arch.to_str().unwrap()).unwrap()
+            .test_archive()
+        };
+        let report = test()?;
+        assert!(report.passed());
+        assert_eq!(report.files.len(), FILES.len());
+
+        // Damage data in the middle of the text file
+        let mut data = fs::read(&arch).unwrap();
+        let text = fs::read(input.join("text.txt")).unwrap();
+        let pos = data.windows(64).position(|w| w == &text[12000..12064]).unwrap();
+        data[pos] ^= 1;
+        fs::write(&arch, data).unwrap();
+
+        let report = test()?;
+        assert!(!report.passed());
+        assert_eq!(report.blocks.iter().filter(|(_, ok)| !ok).count(), 1);
+        for (path, ok) in report.files.iter() {
+            assert_eq!(*ok, !path.ends_with("text.txt"));
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn delete() -> Result<(), PrisirvError> {
+        let dir = test_dir("prisirv_delete");
+        let input = dir.join("in");
+        let arch = dir.join("in.prsv");
+
+        // Small blocks so some blocks hold parts of several files
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .password("secret")
+        .block_size(4096)
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        let deleted = Prisirv::default()
+        .quiet()
+        .password("secret")
+        .arch(arch.to_str().unwrap())?
+        .entries(&["*.bin", "nothing"])
+        .delete_files()?;
+        assert_eq!(deleted, vec![input.join("data.bin")]);
+
+        let report = Prisirv::default()
+        .quiet()
+        .password("secret")
+        .arch(arch.to_str().unwrap())?
+        .test_archive()?;
+        assert!(report.passed());
+        assert!(report.blocks.iter().enumerate().all(|(i, (id, _))| i as u32 == *id));
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .password("secret")
+        .output("out")
+        .arch(arch.to_str().unwrap())?
+        .extract_archive()?;
+
+        let out = dir.join("out/in");
+        assert!(!out.join("data.bin").exists());
+        for file in ["text.txt", "prog.exe"] {
+            assert_eq!(
+                fs::read(input.join(file)).unwrap(),
+                fs::read(out.join(file)).unwrap()
+            );
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn delete_links() -> Result<(), PrisirvError> {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = test_dir("prisirv_delete_links");
+        let input = dir.join("in");
+        let arch = dir.join("in.prsv");
+        let key = dir.join("release");
+        Prisirv::default().signing().output(key.to_str().unwrap()).generate_keys()?;
+
+        // Links in a subdirectory come after the file they link to
+        fs::create_dir(input.join("sub")).unwrap();
+        for name in ["one.bin", "two.bin"] {
+            fs::hard_link(input.join("data.bin"), input.join("sub").join(name)).unwrap();
+        }
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .detached()
+        .sign(dir.join("release.key").to_str().unwrap())?
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+        assert!(dir.join("in.prsv.sig").exists());
+
+        let deleted = Prisirv::default()
+        .quiet()
+        .arch(arch.to_str().unwrap())?
+        .entries(&[input.join("data.bin").to_str().unwrap()])
+        .delete_files()?;
+        assert_eq!(deleted, vec![input.join("data.bin")]);
+        assert!(!dir.join("in.prsv.sig").exists());
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("out")
+        .arch(arch.to_str().unwrap())?
+        .extract_archive()?;
+
+        let out = dir.join("out/in");
+        assert!(!out.join("data.bin").exists());
+        for name in ["one.bin", "two.bin"] {
+            assert_eq!(
+                fs::read(input.join("data.bin")).unwrap(),
+                fs::read(out.join("sub").join(name)).unwrap()
+            );
+        }
+        assert_eq!(
+            fs::metadata(out.join("sub/one.bin")).unwrap().ino(), 
+            fs::metadata(out.join("sub/two.bin")).unwrap().ino()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn update() -> Result<(), PrisirvError> {
+        let dir = test_dir("prisirv_update");
+        let input = dir.join("in");
+        let arch = dir.join("in.prsv");
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        let update = |checksum: bool| -> Result<Vec<PathBuf>, PrisirvError> {
+            let mut prisirv = Prisirv::default()
+            .quiet()
+            .arch(arch.to_str().unwrap())?
+            .inputs(&[input.to_str().unwrap()])?;
+            if checksum {
+                prisirv = prisirv.checksum();
+            }
+            Ok(prisirv.update_files()?)
+        };
+        assert!(update(false)?.is_empty());
+
+        // A new file and a changed file
+        fs::write(input.join("new.txt"), "a new file").unwrap();
+        let mut text = fs::read(input.join("text.txt")).unwrap();
+        text.extend_from_slice(b"more text");
+        fs::write(input.join("text.txt"), &text).unwrap();
+
+        let mut appended = update(false)?;
+        appended.sort();
+        // The directory's modification time changed along with its contents
+        assert_eq!(appended, vec![input.clone(), input.join("new.txt"), input.join("text.txt")]);
+        assert!(update(false)?.is_empty());
+
+        // Same length and modification time, only caught by checksum
+        let path = input.join("data.bin");
+        let mtime = fs::metadata(&path).unwrap().modified().unwrap();
+        let mut data = fs::read(&path).unwrap();
+        data[0] ^= 1;
+        fs::write(&path, &data).unwrap();
+        fs::File::options().write(true).open(&path).unwrap().set_modified(mtime).unwrap();
+        assert!(update(false)?.is_empty());
+        assert_eq!(update(true)?, vec![path.clone()]);
+
+        // Newest entries are extracted last
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("out")
+        .arch(arch.to_str().unwrap())?
+        .extract_archive()?;
+        for file in ["text.txt", "data.bin", "new.txt"] {
+            assert_eq!(
+                fs::read(input.join(file)).unwrap(),
+                fs::read(dir.join("out/in").join(file)).unwrap()
+            );
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn salvage() -> Result<(), PrisirvError> {
+        let dir = test_dir("prisirv_salvage");
+        let input = dir.join("in");
+        let arch = dir.join("in.prsv");
+        fs::write(input.join("note.txt"), "a note that fits in one block").unwrap();
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .method(Method::Store)
+        .block_size(4096)
+        .inputs(&[input.to_str().unwrap()])?
+        .create_archive()?;
+
+        // Damage the header of a block in the middle of the text file,
+        // and some data of the binary file.
+        let mut data = fs::read(&arch).unwrap();
+        let find = |data: &[u8], needle: &[u8]| {
+            data.windows(needle.len()).position(|w| w == needle).unwrap()
+        };
+        let text = fs::read(input.join("text.txt")).unwrap();
+        let pos = find(&data, &text[12000..12064]);
+        let header = data[..pos].windows(4).rposition(|w| w == b"PRSV").unwrap();
+        data[header..header + 16].fill(0);
+
+        let bin = fs::read(input.join("data.bin")).unwrap();
+        let pos = find(&data, &bin[2500..2564]);
+        data[pos..pos + 8].fill(0);
+
+        // Damage the note, so its block fails and takes its file table along
+        let pos = find(&data, b"a note that fits");
+        data[pos..pos + 8].fill(0);
+        fs::write(&arch, data).unwrap();
+
+        let incomplete = Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("out")
+        .arch(arch.to_str().unwrap())?
+        .salvage_archive()?;
+
+        assert_eq!(incomplete, vec![
+            input.join("data.bin"), input.join("note.txt"), input.join("text.txt")
+        ]);
+        assert_eq!(
+            fs::read(input.join("prog.exe")).unwrap(),
+            fs::read(dir.join("out/in/prog.exe")).unwrap()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn content_sniffing() {
+        let dir = test_dir("prisirv_sniff");
+        let files: [(&str, &[u8], Type); 5] = [
+            ("noext",      b"\x7FELF\x02\x01\x01\x00",       Type::Executable),
+            ("image.txt",  b"\x89PNG\r\n\x1A\n\x00\x00",     Type::Compressed),
+            ("data.gz.1",  b"\x1F\x8B\x08\x00\x00\x00",       Type::Compressed),
+            ("notes.bin",  "plain text, héllo\n".as_bytes(), Type::Text),
+            ("blob.txt",   b"\x00\x01\x02\x03\xFE\xFF",       Type::Binary),
+        ];
+        for (name, data, kind) in files {
+            fs::write(dir.join(name), data).unwrap();
+            assert!(FileData::new(dir.join(name)).kind == kind, "{}", name);
+        }
+        fs::write(dir.join("app"), pe_header()).unwrap();
+        assert!(FileData::new(dir.join("app")).kind == Type::Executable);
+        fs::remove_dir_all(&dir).unwrap();
+    }
 
     #[test]
     fn append() -> Result<(), PrisirvError> {
@@ -44,4 +1388,66 @@ mod tests {
         assert!(asyoulik_crc == 0x015E5966);
         Ok(())
     }
+
+    #[test]
+    fn old_version() -> Result<(), PrisirvError> {
+        let dir = test_dir("prisirv_old_version");
+        let input = dir.join("in");
+        let arch = dir.join("old.prsv");
+        let text = fs::read(input.join("text.txt")).unwrap();
+
+        // Block header of the 0.2 layout, with no growth, filter, 
+        // encryption or per file fields
+        let header = |id: u32, data: &Vec<u8>, files: u32| {
+            let mut blk = 0x5653_5250u32.to_le_bytes().to_vec();
+            for ver in [0u16, 2, 0] {
+                blk.extend_from_slice(&ver.to_le_bytes());
+            }
+            blk.extend_from_slice(&(1u64 << 22).to_le_bytes());
+            blk.extend_from_slice(&(1u64 << 20).to_le_bytes());
+            blk.push(1); // Store
+            blk.extend_from_slice(&id.to_le_bytes());
+            blk.extend_from_slice(&data.crc32().to_le_bytes());
+            blk.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            blk.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            blk.extend_from_slice(&0u64.to_le_bytes());
+            blk.extend_from_slice(&files.to_le_bytes());
+            blk
+        };
+        let mut data = header(0, &text, 1);
+        data.extend_from_slice(b"old.txt\0");
+        for field in [text.len() as u64, 0, text.len() as u64, 0] {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        data.extend_from_slice(&text);
+        data.extend_from_slice(&header(1, &Vec::new(), 0));
+        fs::write(&arch, data).unwrap();
+
+        // Blocks of the current version can be appended to old archives
+        Prisirv::default()
+        .quiet()
+        .arch(arch.to_str().unwrap())?
+        .inputs(&[input.join("data.bin").to_str().unwrap()])?
+        .append_files()?;
+
+        let report = Prisirv::default()
+        .quiet()
+        .arch(arch.to_str().unwrap())?
+        .test_archive()?;
+        assert!(report.passed());
+        assert_eq!(report.blocks.len(), 2);
+
+        Prisirv::default()
+        .quiet()
+        .clobber()
+        .output("out")
+        .arch(arch.to_str().unwrap())?
+        .extract_archive()?;
+
+        assert_eq!(fs::read(dir.join("out/old.txt")).unwrap(), text);
+        assert_eq!(fs::read(dir.join("out/in/data.bin")).unwrap(), fs::read(input.join("data.bin")).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+        Ok(())
+    }
 }